@@ -1,4 +1,22 @@
+DROP TABLE IF EXISTS message_events;
+DROP TABLE IF EXISTS outbox;
 DROP TABLE IF EXISTS messages;
+DROP TABLE IF EXISTS api_keys;
+DROP TABLE IF EXISTS rate_limits;
+DROP TABLE IF EXISTS withdrawn;
+DROP TABLE IF EXISTS receipts;
+DROP TABLE IF EXISTS exhausted;
+DROP TABLE IF EXISTS disclose_sessions;
+
+CREATE TABLE api_keys (
+  id SERIAL PRIMARY KEY,
+  name VARCHAR NOT NULL,
+  key_hash VARCHAR(64) NOT NULL UNIQUE,
+  sender_pattern VARCHAR NOT NULL,
+  daily_quota INTEGER,
+  allowed_attributes VARCHAR[],
+  created DATE DEFAULT CURRENT_DATE
+);
 
 CREATE TABLE messages (
   id VARCHAR(32) PRIMARY KEY,
@@ -6,5 +24,68 @@ CREATE TABLE messages (
   to_address VARCHAR NOT NULL,
   subject VARCHAR NOT NULL,
   signature VARCHAR,
+  api_key INTEGER REFERENCES api_keys (id) ON DELETE SET NULL,
+  management_token VARCHAR(64),
+  downloads_left INTEGER,
+  until_decrypted BOOLEAN NOT NULL DEFAULT false,
+  access_secret VARCHAR(64),
+  locale VARCHAR(2) NOT NULL DEFAULT 'nl',
+  link_only BOOLEAN NOT NULL DEFAULT false,
+  channel VARCHAR NOT NULL DEFAULT 'email',
+  sender_copy BOOLEAN NOT NULL DEFAULT false,
+  attributes JSONB,
+  timestamp BIGINT,
   created DATE DEFAULT CURRENT_DATE
 );
+
+CREATE TABLE rate_limits (
+  key VARCHAR PRIMARY KEY,
+  tokens DOUBLE PRECISION NOT NULL,
+  updated TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE TABLE outbox (
+  id SERIAL PRIMARY KEY,
+  message VARCHAR(32) NOT NULL REFERENCES messages (id) ON DELETE CASCADE,
+  kind VARCHAR NOT NULL,
+  status VARCHAR NOT NULL DEFAULT 'pending',
+  attempts INTEGER NOT NULL DEFAULT 0,
+  next_attempt TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+  last_error VARCHAR,
+  sent TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX outbox_due ON outbox (next_attempt) WHERE status = 'pending';
+
+CREATE TABLE withdrawn (
+  id VARCHAR(32) PRIMARY KEY,
+  withdrawn TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE TABLE exhausted (
+  id VARCHAR(32) PRIMARY KEY,
+  exhausted TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE TABLE message_events (
+  id SERIAL PRIMARY KEY,
+  message VARCHAR(32) NOT NULL REFERENCES messages (id) ON DELETE CASCADE,
+  event VARCHAR NOT NULL,
+  created TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE TABLE receipts (
+  message VARCHAR(32) PRIMARY KEY,
+  from_address VARCHAR NOT NULL,
+  to_address VARCHAR NOT NULL,
+  management_token VARCHAR(64),
+  hash VARCHAR NOT NULL,
+  time BIGINT NOT NULL,
+  signature JSONB NOT NULL,
+  created TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE TABLE disclose_sessions (
+  token VARCHAR PRIMARY KEY,
+  created TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
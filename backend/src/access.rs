@@ -0,0 +1,36 @@
+use std::convert::Infallible;
+
+use rocket::request::{FromRequest, Outcome, Request};
+
+use crate::id::secret_matches;
+
+// Secret from the fragment of a download link, sent by the recipient's client
+// in the `X-Access-Secret` header. Only its hash is stored, so neither the
+// database nor access logs are enough to fetch a message.
+pub struct AccessSecret(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AccessSecret {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(AccessSecret(
+            request
+                .headers()
+                .get_one("X-Access-Secret")
+                .map(str::to_owned),
+        ))
+    }
+}
+
+impl AccessSecret {
+    // Whether this matches the stored hash, messages from before access secrets
+    // were introduced have none
+    pub fn matches(&self, hash: Option<&str>) -> bool {
+        match (hash, &self.0) {
+            (None, _) => true,
+            (Some(hash), Some(secret)) => secret_matches(secret, hash),
+            (Some(_), None) => false,
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashSet;
+
+use postgres::GenericClient;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use sha2::{Digest, Sha256};
+
+use crate::{config::Config, error::Error, Database};
+
+// Key issued to a system sending messages through the API. Keys are only stored
+// as a hash, see the README on how to issue them.
+#[derive(Debug, Clone)]
+pub struct ApiKey {
+    pub id: i32,
+    sender_pattern: String,
+    daily_quota: Option<i32>,
+    allowed_attributes: Option<HashSet<String>>,
+}
+
+// The party submitting a message, anonymous unless an API key was presented
+#[derive(Debug, Clone)]
+pub enum Sender {
+    Anonymous,
+    Key(ApiKey),
+}
+
+pub fn hash_api_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+// Case-insensitive match of an address against a pattern in which `*` matches
+// any sequence of characters, like `*@example.com`
+fn matches_pattern(pattern: &str, address: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let address = address.to_lowercase();
+
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    if !address.starts_with(first) {
+        return false;
+    }
+
+    let mut rest = &address[first.len()..];
+    let parts: Vec<&str> = parts.collect();
+    match parts.split_last() {
+        None => rest.is_empty(),
+        Some((last, middle)) => {
+            for part in middle {
+                match rest.find(part) {
+                    Some(index) => rest = &rest[index + part.len()..],
+                    None => return false,
+                }
+            }
+            rest.ends_with(last)
+        }
+    }
+}
+
+impl ApiKey {
+    pub fn allows_sender(&self, from: &str) -> bool {
+        matches_pattern(&self.sender_pattern, from)
+    }
+
+    pub fn allows_attribute(&self, attribute: &str) -> bool {
+        match &self.allowed_attributes {
+            Some(allowed) => allowed.contains(attribute),
+            None => true,
+        }
+    }
+
+    // Check whether `count` more messages fit in today's quota, copies for the
+    // sender are not counted. Call this in the transaction adding the messages:
+    // the key is locked until it ends, so concurrent submissions with the same
+    // key are counted one after the other.
+    pub fn check_quota<C: GenericClient>(&self, c: &mut C, count: usize) -> Result<(), Error> {
+        let quota = match self.daily_quota {
+            Some(quota) => quota as i64,
+            None => return Ok(()),
+        };
+
+        c.execute("SELECT id FROM api_keys WHERE id = $1 FOR UPDATE", &[&self.id])?;
+        let sent: i64 = c
+            .query_one(
+                "SELECT COUNT(*) FROM messages WHERE api_key = $1 AND created = CURRENT_DATE AND NOT sender_copy",
+                &[&self.id],
+            )?
+            .get(0);
+
+        if sent + count as i64 > quota {
+            Err(Error::QuotaExceeded)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+async fn lookup(conn: Database, key_hash: String) -> Result<Option<ApiKey>, Error> {
+    Ok(conn
+        .run(move |c| {
+            c.query(
+                "SELECT id, sender_pattern, daily_quota, allowed_attributes FROM api_keys WHERE key_hash = $1",
+                &[&key_hash],
+            )
+        })
+        .await?
+        .get(0)
+        .map(|row| ApiKey {
+            id: row.get(0),
+            sender_pattern: row.get(1),
+            daily_quota: row.get(2),
+            allowed_attributes: row
+                .get::<_, Option<Vec<String>>>(3)
+                .map(|attributes| attributes.into_iter().collect()),
+        }))
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Sender {
+    type Error = Error;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let require_api_key = request
+            .rocket()
+            .state::<Config>()
+            .map(|config| config.require_api_key)
+            .unwrap_or(true);
+
+        let key = match request.headers().get_one("Authorization") {
+            Some(value) => match value.strip_prefix("Bearer ") {
+                Some(key) => key.trim(),
+                None => return Outcome::Failure((Status::Unauthorized, Error::Unauthorized)),
+            },
+            None if require_api_key => {
+                return Outcome::Failure((Status::Unauthorized, Error::Unauthorized))
+            }
+            None => return Outcome::Success(Sender::Anonymous),
+        };
+
+        let conn = match request.guard::<Database>().await {
+            Outcome::Success(conn) => conn,
+            _ => return Outcome::Failure((Status::ServiceUnavailable, Error::MissingData)),
+        };
+
+        match lookup(conn, hash_api_key(key)).await {
+            Ok(Some(api_key)) => Outcome::Success(Sender::Key(api_key)),
+            Ok(None) => Outcome::Failure((Status::Unauthorized, Error::Unauthorized)),
+            Err(e) => Outcome::Failure((Status::InternalServerError, e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::matches_pattern;
+
+    #[test]
+    fn sender_patterns() {
+        assert!(matches_pattern("*@example.com", "payroll@example.com"));
+        assert!(matches_pattern("*@example.com", "Payroll@Example.com"));
+        assert!(matches_pattern("payroll@example.com", "payroll@example.com"));
+        assert!(matches_pattern("*", "anyone@anywhere.org"));
+        assert!(matches_pattern("noreply-*@*.example.com", "noreply-hr@mail.example.com"));
+
+        assert!(!matches_pattern("*@example.com", "payroll@example.com.evil.org"));
+        assert!(!matches_pattern("payroll@example.com", "payroll2@example.com"));
+        assert!(!matches_pattern("noreply-*@*.example.com", "hr@mail.example.com"));
+    }
+}
@@ -0,0 +1,253 @@
+use rocket::request::FromParam;
+
+use crate::events::{record, Event};
+use crate::id::Id;
+use crate::outbox::{self, BOUNCE, NOTIFICATION};
+use crate::{config::Config, error::Error, Database};
+
+// Delivery status notification (RFC 3464), as sent back by mail servers when
+// an email could not be delivered
+#[derive(Debug, PartialEq)]
+pub struct Report {
+    // Message-IDs found in the report, including the one of the returned email
+    pub message_ids: Vec<String>,
+    // Recipients the email could not be delivered to
+    pub failed: Vec<String>,
+}
+
+// Whether the Content-Type of an email is that of a delivery status report
+pub fn is_report(content_type: &str) -> bool {
+    let content_type = content_type.to_ascii_lowercase();
+    content_type.starts_with("multipart/report") && content_type.contains("delivery-status")
+}
+
+// Header lines of a block of text, with folded lines joined
+fn fields(block: &str) -> Vec<(String, String)> {
+    let mut fields: Vec<(String, String)> = Vec::new();
+    for line in block.lines() {
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some((_, value)) = fields.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            fields.push((name.trim().to_ascii_lowercase(), value.trim().to_owned()));
+        }
+    }
+    fields
+}
+
+// Value of a field, without the type prefix of address fields like
+// `Final-Recipient: rfc822; jane@example.com`
+fn field<'a>(fields: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    fields
+        .iter()
+        .find(|(field, _)| field == name)
+        .map(|(_, value)| match value.split_once(';') {
+            Some((_, address)) => address.trim(),
+            None => value.as_str(),
+        })
+}
+
+// Parse the source of a delivery status report. The per-recipient fields are
+// read from the `message/delivery-status` part, the Message-IDs from any part.
+pub fn parse(source: &str) -> Option<Report> {
+    let source = source.replace("\r\n", "\n");
+
+    let message_ids = source
+        .split("\n\n")
+        .flat_map(|block| fields(block).into_iter())
+        .filter(|(name, _)| name == "message-id")
+        .map(|(_, value)| value)
+        .collect();
+
+    // The status part starts after its own headers, and ends at the next
+    // boundary of the multipart report
+    let lower = source.to_ascii_lowercase();
+    let part = lower.find("content-type: message/delivery-status")?;
+    let start = part + source[part..].find("\n\n")? + 2;
+    let end = source[start..]
+        .find("\n--")
+        .map_or(source.len(), |end| start + end);
+
+    // The first group of fields is about the report, the others are about a
+    // recipient each
+    let failed = source[start..end]
+        .split("\n\n")
+        .map(fields)
+        .filter(|fields| {
+            field(fields, "action").map_or(false, |action| action.eq_ignore_ascii_case("failed"))
+        })
+        .filter_map(|fields| {
+            field(&fields, "final-recipient")
+                .or_else(|| field(&fields, "original-recipient"))
+                .map(str::to_owned)
+        })
+        .collect::<Vec<_>>();
+
+    if failed.is_empty() {
+        None
+    } else {
+        Some(Report {
+            message_ids,
+            failed,
+        })
+    }
+}
+
+// Id of the message a notification was sent for, from its Message-ID
+fn notification_id(message_id: &str, domain: &str) -> Option<Id> {
+    let message_id = message_id.trim().trim_start_matches('<').trim_end_matches('>');
+    let (local, message_domain) = message_id.rsplit_once('@')?;
+    if !message_domain.eq_ignore_ascii_case(domain) {
+        return None;
+    }
+    Id::from_param(local).ok()
+}
+
+// Mark the notifications in a report as bounced, and queue an email telling
+// the sender which recipient was not reached. Only senders using an API key are
+// emailed, as the key restricts the sender address; others could have entered
+// anyone's address, and see the bounce in the delivery status instead. Reports
+// about other emails, or about recipients other than that of the message, are
+// ignored.
+pub async fn process_report(config: &Config, conn: &Database, source: &str) -> Result<(), Error> {
+    let report = parse(source).ok_or(Error::MissingData)?;
+    let domain = config.mail_user.email.domain();
+
+    for id in report
+        .message_ids
+        .iter()
+        .filter_map(|message_id| notification_id(message_id, domain))
+    {
+        let id = id.to_string();
+        let failed = report.failed.clone();
+        conn.run(move |c| -> Result<(), Error> {
+            let mut transaction = c.transaction()?;
+            let (to, api_key): (String, bool) = match transaction
+                .query(
+                    "SELECT to_address, api_key IS NOT NULL FROM messages WHERE id = $1",
+                    &[&id],
+                )?
+                .get(0)
+            {
+                Some(row) => (row.get(0), row.get(1)),
+                None => return Ok(()),
+            };
+            if !failed.iter().any(|address| address.eq_ignore_ascii_case(&to)) {
+                log::warn!("Ignoring bounce for {} about another recipient", id);
+                return Ok(());
+            }
+
+            // Only the first report for a notification is handled
+            let bounced = transaction.execute(
+                "UPDATE outbox SET status = 'bounced' WHERE message = $1 AND kind = $2 AND status = 'sent'",
+                &[&id, &NOTIFICATION],
+            )?;
+            if bounced > 0 {
+                record(&mut transaction, &id, Event::NotificationBounced)?;
+                if api_key {
+                    outbox::enqueue(&mut transaction, &id, BOUNCE)?;
+                }
+            }
+            transaction.commit()?;
+            Ok(())
+        })
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::{is_report, notification_id, parse, Report};
+
+    // Report as sent by Postfix for a notification to an unknown recipient
+    pub fn report(id: &str, recipient: &str) -> String {
+        format!(
+            "From: Mail Delivery System <MAILER-DAEMON@mx.example.net>\r
+To: test@example.com\r
+Subject: Undelivered Mail Returned to Sender\r
+Message-ID: <20211018101010.ABCDEF@mx.example.net>\r
+MIME-Version: 1.0\r
+Content-Type: multipart/report; report-type=delivery-status;\r
+\tboundary=\"boundary\"\r
+\r
+--boundary\r
+Content-Type: text/plain; charset=us-ascii\r
+\r
+I'm sorry to have to inform you that your message could not\r
+be delivered to one or more recipients.\r
+\r
+--boundary\r
+Content-Type: message/delivery-status\r
+\r
+Reporting-MTA: dns; mx.example.net\r
+Arrival-Date: Mon, 18 Oct 2021 10:10:10 +0200 (CEST)\r
+\r
+Final-Recipient: rfc822; {recipient}\r
+Original-Recipient: rfc822;{recipient}\r
+Action: failed\r
+Status: 5.1.1\r
+Diagnostic-Code: smtp; 550 5.1.1 <{recipient}>: Recipient address rejected:\r
+    User unknown\r
+\r
+--boundary\r
+Content-Type: text/rfc822-headers\r
+\r
+From: test@example.com\r
+Reply-To: from@example.com\r
+To: {recipient}\r
+Subject: Example subject\r
+Message-ID:\r
+ <{id}@example.com>\r
+\r
+--boundary--\r
+",
+            id = id,
+            recipient = recipient
+        )
+    }
+
+    #[test]
+    fn parses_report() {
+        let id = "abcdefghijklmnopqrstuvwxyz012345";
+        let report = parse(&report(id, "to@example.com")).unwrap();
+        assert_eq!(
+            report,
+            Report {
+                message_ids: vec![
+                    "<20211018101010.ABCDEF@mx.example.net>".to_owned(),
+                    format!("<{}@example.com>", id),
+                ],
+                failed: vec!["to@example.com".to_owned()],
+            }
+        );
+
+        let ids: Vec<_> = report
+            .message_ids
+            .iter()
+            .filter_map(|message_id| notification_id(message_id, "example.com"))
+            .map(|id| id.to_string())
+            .collect();
+        assert_eq!(ids, vec![id.to_owned()]);
+        assert!(notification_id(&report.message_ids[1], "example.org").is_none());
+    }
+
+    #[test]
+    fn ignores_delivered() {
+        let delivered = report("abcdefghijklmnopqrstuvwxyz012345", "to@example.com")
+            .replace("Action: failed", "Action: delivered");
+        assert_eq!(parse(&delivered), None);
+        assert_eq!(parse("Subject: Hello\r\n\r\nNot a report"), None);
+    }
+
+    #[test]
+    fn report_content_type() {
+        assert!(is_report(
+            "multipart/report; report-type=delivery-status; boundary=\"x\""
+        ));
+        assert!(!is_report("multipart/mixed; boundary=\"x\""));
+    }
+}
@@ -1,8 +1,21 @@
+use crate::dkim;
+use crate::error::Error;
+use crate::mailer::{HttpMailer, MaildirMailer, Mailer, MemoryMailer};
+use crate::notifier::{EmailNotifier, Notifier, SmsGateway, Webhook};
+use crate::ratelimit::{RateLimitConfig, RateLimiter};
 use crate::storage::{CloudStorage, LocalStorage, Storage};
-use std::{collections::HashSet, convert::TryFrom, iter::FromIterator};
+use crate::templates::Templates;
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    iter::FromIterator,
+    path::PathBuf,
+};
 
+use common::Channel;
 use irma::{IrmaClient, IrmaClientBuilder};
-use lettre::{message::Mailbox, transport::smtp::authentication::Credentials, SmtpTransport};
+use lettre::message::{dkim::DkimConfig, Mailbox};
+use lettre::{transport::smtp::authentication::Credentials, SmtpTransport};
 use log::warn;
 use serde::Deserialize;
 
@@ -14,13 +27,44 @@ enum StorageType {
     Gcs,
 }
 
+#[derive(Deserialize)]
+enum MailTransport {
+    #[serde(rename = "smtp")]
+    Smtp,
+    #[serde(rename = "http")]
+    Http,
+    #[serde(rename = "maildir")]
+    Maildir,
+    #[serde(rename = "memory")]
+    Memory,
+}
+
+impl Default for MailTransport {
+    fn default() -> Self {
+        MailTransport::Smtp
+    }
+}
+
 #[derive(Deserialize)]
 struct RawConfig {
     host: String,
     mail_user: String,
+    #[serde(default)]
+    mail_transport: MailTransport,
     mail_pass: Option<String>,
-    mail_host: String,
-    mail_port: u16,
+    mail_host: Option<String>,
+    mail_port: Option<u16>,
+    mail_api_url: Option<String>,
+    mail_api_key: Option<String>,
+    mail_directory: Option<String>,
+    dkim_selector: Option<String>,
+    dkim_domain: Option<String>,
+    dkim_private_key: Option<String>,
+    sms_gateway_url: Option<String>,
+    sms_gateway_key: Option<String>,
+    sms_sender: Option<String>,
+    #[serde(default)]
+    webhooks: HashMap<String, Webhook>,
     sentry_dsn: Option<String>,
     mailgun_key: Option<String>,
     mailgun_message_url_prefix: String,
@@ -32,6 +76,30 @@ struct RawConfig {
     irmaserver: String,
     irmaserver_token: Option<String>,
     maximum_file_size: usize,
+    #[serde(default = "default_maximum_attachment_size")]
+    maximum_attachment_size: usize,
+    #[serde(default)]
+    require_api_key: bool,
+    #[serde(default)]
+    rate_limit: RateLimitConfig,
+    #[serde(default = "default_outbox_worker")]
+    outbox_worker: bool,
+    #[serde(default = "default_outbox_max_attempts")]
+    outbox_max_attempts: i32,
+    url_signing_key: Option<String>,
+    template_dir: Option<String>,
+}
+
+fn default_outbox_worker() -> bool {
+    true
+}
+
+fn default_outbox_max_attempts() -> i32 {
+    8
+}
+
+fn default_maximum_attachment_size() -> usize {
+    1_000_000
 }
 
 #[derive(Deserialize)]
@@ -39,7 +107,10 @@ struct RawConfig {
 pub struct Config {
     pub host: String,
     pub mail_user: Mailbox,
-    pub mailer: SmtpTransport,
+    pub mailer: Box<dyn Mailer>,
+    pub dkim: Option<DkimConfig>,
+    pub sms_gateway: Option<SmsGateway>,
+    pub webhooks: HashMap<String, Webhook>,
     pub sentry_dsn: Option<String>,
     pub mailgun_key: Option<String>,
     pub mailgun_message_url_prefix: String,
@@ -49,25 +120,85 @@ pub struct Config {
     pub allowed_signing_attributes: HashSet<String>,
     pub irmaserver: IrmaClient,
     pub maximum_file_size: usize,
+    pub maximum_attachment_size: usize,
+    pub require_api_key: bool,
+    pub rate_limiter: RateLimiter,
+    pub outbox_worker: bool,
+    pub outbox_max_attempts: i32,
+    pub url_signing_key: Vec<u8>,
+    pub templates: Templates,
 }
 
 impl TryFrom<RawConfig> for Config {
-    type Error = crate::error::Error;
+    type Error = Error;
 
     fn try_from(v: RawConfig) -> Result<Self, Self::Error> {
-        let mailer = match v.mail_pass {
-            Some(pass) => {
-                let credentials = Credentials::new(v.mail_user.clone(), pass);
-                SmtpTransport::starttls_relay(&v.mail_host)?
-                    .port(v.mail_port)
-                    .credentials(credentials)
-                    .build()
+        let mailer: Box<dyn Mailer> = match v.mail_transport {
+            MailTransport::Smtp => {
+                let (host, port) = match (v.mail_host, v.mail_port) {
+                    (Some(host), Some(port)) => (host, port),
+                    _ => {
+                        log::error!("The smtp mail transport requires mail_host and mail_port");
+                        return Err(Error::Configuration);
+                    }
+                };
+                Box::new(match v.mail_pass {
+                    Some(pass) => {
+                        let credentials = Credentials::new(v.mail_user.clone(), pass);
+                        SmtpTransport::starttls_relay(&host)?
+                            .port(port)
+                            .credentials(credentials)
+                            .build()
+                    }
+                    None => {
+                        warn!("Warning: No email password specified, using insecure mailer");
+                        SmtpTransport::builder_dangerous(host).port(port).build()
+                    }
+                })
             }
-            None => {
-                warn!("Warning: No email password specified, using insecure mailer");
-                SmtpTransport::builder_dangerous(v.mail_host)
-                    .port(v.mail_port)
-                    .build()
+            MailTransport::Http => match (v.mail_api_url, v.mail_api_key) {
+                (Some(url), Some(key)) => Box::new(HttpMailer { url, key }),
+                _ => {
+                    log::error!("The http mail transport requires mail_api_url and mail_api_key");
+                    return Err(Error::Configuration);
+                }
+            },
+            MailTransport::Maildir => match v.mail_directory {
+                Some(directory) => Box::new(MaildirMailer {
+                    directory: PathBuf::from(directory),
+                }),
+                None => {
+                    log::error!("The maildir mail transport requires mail_directory");
+                    return Err(Error::Configuration);
+                }
+            },
+            MailTransport::Memory => {
+                warn!("Warning: Emails are kept in memory and not delivered");
+                Box::new(MemoryMailer)
+            }
+        };
+
+        let dkim = match (v.dkim_selector, v.dkim_domain, v.dkim_private_key) {
+            (Some(selector), Some(domain), Some(key)) => {
+                Some(dkim::config(selector, domain, &key)?)
+            }
+            (None, None, None) => None,
+            _ => {
+                log::error!(
+                    "DKIM signing requires dkim_selector, dkim_domain and dkim_private_key"
+                );
+                return Err(Error::Configuration);
+            }
+        };
+
+        let sms_gateway = match (v.sms_gateway_url, v.sms_gateway_key, v.sms_sender) {
+            (Some(url), Some(key), Some(sender)) => Some(SmsGateway { url, key, sender }),
+            (None, None, None) => None,
+            _ => {
+                log::error!(
+                    "SMS notifications require sms_gateway_url, sms_gateway_key and sms_sender"
+                );
+                return Err(Error::Configuration);
             }
         };
 
@@ -78,12 +209,21 @@ impl TryFrom<RawConfig> for Config {
             None => IrmaClient::new(&v.irmaserver)?,
         };
 
+        let url_signing_key = match v.url_signing_key {
+            Some(key) => key.into_bytes(),
+            None => {
+                warn!("Warning: No url signing key specified, using a random key");
+                rand::random::<[u8; 32]>().to_vec()
+            }
+        };
+
         let mail_user: Mailbox = v.mail_user.parse()?;
         Ok(Config {
             storage: match v.storage_type {
                 StorageType::Local => Box::new(LocalStorage {
                     host: v.host.clone(),
                     directory: v.storage_location,
+                    signing_key: url_signing_key.clone(),
                 }),
                 StorageType::Gcs => Box::new(CloudStorage {
                     bucket: v.storage_location,
@@ -92,6 +232,9 @@ impl TryFrom<RawConfig> for Config {
             host: v.host,
             mail_user,
             mailer,
+            dkim,
+            sms_gateway,
+            webhooks: v.webhooks,
             sentry_dsn: v.sentry_dsn,
             mailgun_key: v.mailgun_key,
             mailgun_message_url_prefix: v.mailgun_message_url_prefix,
@@ -102,6 +245,25 @@ impl TryFrom<RawConfig> for Config {
             ),
             irmaserver,
             maximum_file_size: v.maximum_file_size,
+            maximum_attachment_size: v.maximum_attachment_size,
+            require_api_key: v.require_api_key,
+            rate_limiter: v.rate_limit.into(),
+            outbox_worker: v.outbox_worker,
+            outbox_max_attempts: v.outbox_max_attempts,
+            url_signing_key,
+            templates: Templates::new(v.template_dir.as_deref())?,
         })
     }
 }
+
+impl Config {
+    // Notifier for a recipient's channel, if that channel is configured
+    pub fn notifier(&self, channel: &Channel) -> Option<&dyn Notifier> {
+        let notifier: &dyn Notifier = match channel {
+            Channel::Email => &EmailNotifier,
+            Channel::Sms => self.sms_gateway.as_ref()?,
+            Channel::Webhook(name) => self.webhooks.get(name)?,
+        };
+        Some(notifier)
+    }
+}
@@ -2,77 +2,173 @@ use crate::id::Id;
 use crate::Config;
 use crate::Error;
 
+use common::Locale;
 use lettre::{
     message::{
-        header::{ContentDisposition, ContentTransferEncoding},
-        MaybeString, MultiPart, SinglePart, SinglePartBuilder,
+        header::{self, ContentDisposition, ContentTransferEncoding, ContentType},
+        Body, MessageBuilder, MultiPart, SinglePartBuilder,
     },
-    Message, Transport,
+    Message,
 };
+use tera::Context;
+
+// Sign the email when DKIM is configured, and hand it to the mail transport
+fn deliver(config: &Config, mut email: Message) -> Result<(), Error> {
+    if let Some(dkim) = &config.dkim {
+        email.sign(dkim);
+    }
+    config.mailer.send(&email)
+}
+
+// Message with a multipart body. Lettre writes the Content-Type of a multipart
+// body in front of the body, where the DKIM signature does not cover it, so it
+// is moved to the message headers instead.
+pub fn multipart(builder: MessageBuilder, body: MultiPart) -> Result<Message, Error> {
+    let formatted = body.formatted();
+    let start = formatted
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map_or(0, |end| end + 4);
+
+    let mut builder = builder.header(header::MIME_VERSION_1_0);
+    if let Some(content_type) = body.headers().get::<ContentType>() {
+        builder = builder.header(content_type);
+    }
+    Ok(builder.body(Body::dangerous_pre_encoded(
+        formatted[start..].to_vec(),
+        ContentTransferEncoding::EightBit,
+    ))?)
+}
+
+// Link to the download page in the recipient's language, the access secret is
+// in the fragment so that it is never sent to the server as part of the url
+pub fn download_url(
+    config: &Config,
+    id: &Id,
+    access_secret: Option<&str>,
+    locale: Locale,
+) -> String {
+    let url = format!("{}/{}/download/{}", config.host, locale.code(), id);
+    match access_secret {
+        Some(secret) => format!("{}#{}", url, secret),
+        None => url,
+    }
+}
+
+// Message-ID of the notification for a message, bounces are matched to the
+// message through it
+pub fn notification_message_id(config: &Config, id: &Id) -> String {
+    format!("<{}@{}>", id, config.mail_user.email.domain())
+}
 
 pub fn send_email(
     config: &Config,
     id: &Id,
+    access_secret: Option<&str>,
+    locale: Locale,
     from: &str,
     to: &str,
     subject: &str,
-    body: Vec<u8>,
+    body: Option<Vec<u8>>,
 ) -> Result<(), Error> {
-    let url = format!("{}/download/{}", config.host, id);
-    let text_body = include_str!("message.txt")
-        .to_string()
-        .replace(":from", from)
-        .replace(":url", &url);
-
-    let seal = SinglePartBuilder::new()
-        .content_type(
-            "application/irmaseal; name=\"irmaseal.encrypted\""
-                .parse()
-                .unwrap(),
-        )
-        .header(ContentDisposition::attachment("encrypted.irmaseal"))
-        .header(ContentTransferEncoding::Base64)
-        .body(MaybeString::Binary(body));
-
-    let text = SinglePart::plain(text_body);
-
-    // let html = SinglePart::html(html_body);
-    // let fallback = MultiPart::alternative_plain_html(text, html);
-
-    let fallback = text;
-
-    let content = MultiPart::mixed().singlepart(seal).singlepart(fallback);
-
-    let email = Message::builder()
-        .from(config.mail_user.clone())
-        .reply_to(from.parse()?)
-        .to(to.parse()?)
-        .subject(subject)
-        .multipart(content)
-        .unwrap();
-
-    config.mailer.send(&email)?;
-    Ok(())
+    // Large messages are only linked to, as mail servers may reject them
+    let attachment = body.filter(|body| body.len() <= config.maximum_attachment_size);
+
+    let mut context = Context::new();
+    context.insert("from", from);
+    context.insert("subject", subject);
+    context.insert("url", &download_url(config, id, access_secret, locale));
+    context.insert("attached", &attachment.is_some());
+
+    let body = MultiPart::alternative_plain_html(
+        config.templates.render(locale, "message.txt", &context)?,
+        config.templates.render(locale, "message.html", &context)?,
+    );
+
+    let mut content = MultiPart::mixed().multipart(body);
+    if let Some(attachment) = attachment {
+        let seal = SinglePartBuilder::new()
+            .content_type(
+                "application/irmaseal; name=\"irmaseal.encrypted\""
+                    .parse()
+                    .unwrap(),
+            )
+            .header(ContentDisposition::attachment("encrypted.irmaseal"))
+            .header(ContentTransferEncoding::Base64)
+            .body(attachment);
+        content = content.singlepart(seal);
+    }
+
+    let email = multipart(
+        Message::builder()
+            .from(config.mail_user.clone())
+            .reply_to(from.parse()?)
+            .to(to.parse()?)
+            .subject(subject)
+            .message_id(Some(notification_message_id(config, id))),
+        content,
+    )?;
+
+    deliver(config, email)
 }
 
 pub fn send_confirmation_email(
     config: &Config,
     id: Id,
+    access_secret: Option<&str>,
+    locale: Locale,
+    to: &str,
+    subject: &str,
+) -> Result<(), Error> {
+    let mut context = Context::new();
+    context.insert("subject", subject);
+    context.insert("url", &download_url(config, &id, access_secret, locale));
+
+    let body = MultiPart::alternative_plain_html(
+        config.templates.render(locale, "confirm_message.txt", &context)?,
+        config.templates.render(locale, "confirm_message.html", &context)?,
+    );
+
+    let email = multipart(
+        Message::builder()
+            .from(config.mail_user.clone())
+            .to(to.parse()?)
+            .subject(format!("Re: {}", subject)),
+        body,
+    )?;
+
+    deliver(config, email)
+}
+
+// Tell the sender that the notification for one of the recipients bounced,
+// only sent for messages submitted with an API key that vouches for `from`
+pub fn send_bounce_email(
+    config: &Config,
+    locale: Locale,
+    from: &str,
     to: &str,
     subject: &str,
 ) -> Result<(), Error> {
-    let url = format!("{}/download/{}", config.host, id);
-    let body = include_str!("confirm_message.txt")
-        .to_string()
-        .replace(":url", &url);
+    let mut context = Context::new();
+    context.insert("subject", subject);
+    context.insert("to", to);
 
-    let email = Message::builder()
-        .from(config.mail_user.clone())
-        .to(to.parse()?)
-        .subject(format!("Re: {}", subject))
-        .body(body)?;
+    let body = MultiPart::alternative_plain_html(
+        config.templates.render(locale, "bounce_message.txt", &context)?,
+        config.templates.render(locale, "bounce_message.html", &context)?,
+    );
 
-    config.mailer.send(&email)?;
+    let prefix = match locale {
+        Locale::Nl => "Niet afgeleverd",
+        Locale::En => "Undeliverable",
+    };
+    let email = multipart(
+        Message::builder()
+            .from(config.mail_user.clone())
+            .to(from.parse()?)
+            .subject(format!("{}: {}", prefix, subject)),
+        body,
+    )?;
 
-    Ok(())
+    deliver(config, email)
 }
@@ -32,6 +32,28 @@ pub enum Error {
     NotFound,
     #[error("Missing data")]
     MissingData,
+    #[error("Unauthorized")]
+    Unauthorized,
+    #[error("Sender address not allowed")]
+    InvalidSender,
+    #[error("Quota exceeded")]
+    QuotaExceeded,
+    #[error("Rate limited")]
+    RateLimited,
+    #[error("Invalid configuration")]
+    Configuration,
+    #[error("Withdrawn")]
+    Withdrawn,
+    #[error("Invalid receipt")]
+    InvalidReceipt,
+    #[error("Download limit exhausted")]
+    Exhausted,
+    #[error("Invalid url signature")]
+    InvalidUrlSignature,
+    #[error("Template error: {0}")]
+    Template(#[from] tera::Error),
+    #[error("Notification channel not available")]
+    UnsupportedChannel,
 }
 
 impl<'r, 'o: 'r> rocket::response::Responder<'r, 'o> for Error {
@@ -42,10 +64,48 @@ impl<'r, 'o: 'r> rocket::response::Responder<'r, 'o> for Error {
                 "Attribute used for encryption is not allowed",
             ))
             .respond_to(request),
-            Error::TooBig => rocket::response::status::BadRequest::<&'static str>(Some(
-                "Encrypted data too large",
+            Error::TooBig => {
+                rocket::response::status::BadRequest::<&'static str>(Some(common::TOO_LARGE))
+                    .respond_to(request)
+            }
+            Error::Unauthorized => {
+                rocket::response::status::Unauthorized::<()>(None).respond_to(request)
+            }
+            Error::InvalidSender => rocket::response::status::Forbidden::<&'static str>(Some(
+                "Sender address is not allowed for this API key",
+            ))
+            .respond_to(request),
+            Error::QuotaExceeded => rocket::response::status::Custom(
+                rocket::http::Status::TooManyRequests,
+                "Daily quota of this API key exceeded",
+            )
+            .respond_to(request),
+            Error::RateLimited => rocket::response::status::Custom(
+                rocket::http::Status::TooManyRequests,
+                "Too many requests, please try again later",
+            )
+            .respond_to(request),
+            Error::Withdrawn => rocket::response::status::Custom(
+                rocket::http::Status::Gone,
+                "This message was withdrawn by the sender",
+            )
+            .respond_to(request),
+            Error::Exhausted => {
+                rocket::response::status::Custom(rocket::http::Status::Gone, common::EXHAUSTED)
+                    .respond_to(request)
+            }
+            Error::InvalidUrlSignature => rocket::response::status::Forbidden::<&'static str>(
+                Some("This url is invalid or expired"),
+            )
+            .respond_to(request),
+            Error::InvalidReceipt => rocket::response::status::BadRequest::<&'static str>(Some(
+                "Receipt signature does not match the message or its recipient",
             ))
             .respond_to(request),
+            Error::UnsupportedChannel => rocket::response::status::BadRequest::<&'static str>(
+                Some("Notification channel is not available"),
+            )
+            .respond_to(request),
             Error::Validation(e) => {
                 rocket::response::status::BadRequest::<String>(Some(e.to_string()))
                     .respond_to(request)
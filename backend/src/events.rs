@@ -0,0 +1,84 @@
+use common::{MessageEvent, MessageStatus};
+use postgres::GenericClient;
+use rocket::{serde::json::Json, State};
+
+use crate::manage::{message_owner, Authorization};
+use crate::{config::Config, error::Error, id::Id, Database};
+
+// Things that happened to a message, as shown to its sender
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    NotificationSent,
+    NotificationBounced,
+    PageFetched,
+    ContentFetched,
+    ReceiptSigned,
+}
+
+impl Event {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Event::NotificationSent => "notification_sent",
+            Event::NotificationBounced => "notification_bounced",
+            Event::PageFetched => "page_fetched",
+            Event::ContentFetched => "content_fetched",
+            Event::ReceiptSigned => "receipt_signed",
+        }
+    }
+}
+
+pub fn record<C: GenericClient>(
+    c: &mut C,
+    message: &str,
+    event: Event,
+) -> Result<(), postgres::Error> {
+    c.execute(
+        "INSERT INTO message_events (message, event) VALUES ($1, $2)",
+        &[&message, &event.as_str()],
+    )?;
+    Ok(())
+}
+
+pub async fn record_event(conn: &Database, id: &Id, event: Event) -> Result<(), Error> {
+    let id = id.to_string();
+    conn.run(move |c| record(c, &id, event)).await?;
+    Ok(())
+}
+
+#[get("/api/message/<id>/status")]
+pub async fn message_status(
+    config: &State<Config>,
+    conn: Database,
+    id: Id,
+    authorization: Authorization,
+) -> Result<Json<MessageStatus>, Error> {
+    let (from, token_hash) = message_owner(&conn, &id).await?;
+    authorization.check(config, &conn, token_hash.as_deref(), &from).await?;
+
+    let row_id = id.to_string();
+    let (to, events) = conn
+        .run(move |c| -> Result<_, Error> {
+            let to: String = c
+                .query_one("SELECT to_address FROM messages WHERE id = $1", &[&row_id])?
+                .get(0);
+            let events = c
+                .query(
+                    "SELECT event, EXTRACT(EPOCH FROM created)::BIGINT FROM message_events WHERE message = $1 ORDER BY created, id",
+                    &[&row_id],
+                )?
+                .iter()
+                .map(|row| MessageEvent {
+                    event: row.get(0),
+                    time: row.get::<_, i64>(1) as u64,
+                })
+                .collect();
+            Ok((to, events))
+        })
+        .await?;
+
+    Ok(Json(MessageStatus {
+        id: id.to_string(),
+        to,
+        events,
+    }))
+}
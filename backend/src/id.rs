@@ -3,17 +3,50 @@ use std::fmt::{Display, Formatter};
 use rand::distributions::Alphanumeric;
 use rand::Rng;
 use rocket::request::FromParam;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+fn random_string(length: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
 
 #[derive(Clone, Debug)]
 pub struct Id(String);
 
 impl Id {
     pub fn new() -> Self {
-        Id(rand::thread_rng()
-            .sample_iter(&Alphanumeric)
-            .take(32)
-            .map(char::from)
-            .collect())
+        Id(random_string(32))
+    }
+}
+
+// Secret handed to the sender to manage a message, only its hash is stored
+pub fn new_management_token() -> String {
+    random_string(48)
+}
+
+// Secret in the fragment of a download link, only its hash is stored
+pub fn new_access_secret() -> String {
+    random_string(32)
+}
+
+pub fn hash_secret(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+// Whether a secret matches its stored hash, compared in constant time so the
+// timing of responses does not reveal how much of the hash matched
+pub fn secret_matches(secret: &str, hash: &str) -> bool {
+    hash_secret(secret).as_bytes().ct_eq(hash.as_bytes()).into()
+}
+
+// Ids read back from the database
+impl From<String> for Id {
+    fn from(id: String) -> Self {
+        Id(id)
     }
 }
 
@@ -0,0 +1,105 @@
+use rocket::State;
+
+use crate::access::AccessSecret;
+use crate::{config::Config, error::Error, id::Id, Database};
+
+// Whether the content of a message may be fetched
+pub enum Access {
+    Unlimited,
+    // Fetching the content is counted, `last` when the limit ran out with it
+    Limited { last: bool },
+}
+
+// Count a fetch of the content of a message against its download limit
+pub async fn take_download(conn: &Database, id: &Id) -> Result<Access, Error> {
+    let id = id.to_string();
+    conn.run(move |c| -> Result<Access, Error> {
+        let rows = c.query(
+            "UPDATE messages SET downloads_left = downloads_left - 1 WHERE id = $1 AND downloads_left > 0 RETURNING downloads_left",
+            &[&id],
+        )?;
+        if let Some(row) = rows.get(0) {
+            return Ok(Access::Limited {
+                last: row.get::<_, i32>(0) == 0,
+            });
+        }
+
+        let rows = c.query(
+            "SELECT downloads_left, until_decrypted FROM messages WHERE id = $1",
+            &[&id],
+        )?;
+        match rows.get(0).map(|row| (row.get::<_, Option<i32>>(0), row.get(1))) {
+            Some((None, false)) => Ok(Access::Unlimited),
+            Some((None, true)) => Ok(Access::Limited { last: false }),
+            Some((Some(_), _)) => Err(Error::Exhausted),
+            None => Err(Error::NotFound),
+        }
+    })
+    .await
+}
+
+// Whether a message has a download limit, its content is then never available
+// through a storage url
+pub async fn is_limited(conn: &Database, id: &str) -> Result<bool, Error> {
+    let id = id.to_owned();
+    Ok(!conn
+        .run(move |c| {
+            c.query(
+                "SELECT 1 FROM messages WHERE id = $1 AND (downloads_left IS NOT NULL OR until_decrypted)",
+                &[&id],
+            )
+        })
+        .await?
+        .is_empty())
+}
+
+// Delete a message of which the download limit ran out
+pub async fn expire(config: &Config, conn: &Database, id: &Id) -> Result<(), Error> {
+    // A tombstone is kept, so downloads can tell the message is gone
+    let row_id = id.to_string();
+    conn.run(move |c| -> Result<(), Error> {
+        let mut transaction = c.transaction()?;
+        transaction.execute("DELETE FROM messages WHERE id = $1", &[&row_id])?;
+        transaction.execute(
+            "INSERT INTO exhausted (id) VALUES ($1) ON CONFLICT DO NOTHING",
+            &[&row_id],
+        )?;
+        transaction.commit()?;
+        Ok(())
+    })
+    .await?;
+
+    config.storage.delete(&id.to_string()).await
+}
+
+// Called by the recipient once a message is decrypted, which deletes messages
+// that may only be downloaded until then
+#[post("/api/download/<id>/decrypted")]
+pub async fn decrypted(
+    config: &State<Config>,
+    conn: Database,
+    id: Id,
+    access_secret: AccessSecret,
+) -> Result<(), Error> {
+    let row_id = id.to_string();
+    let row = conn
+        .run(move |c| {
+            c.query(
+                "SELECT until_decrypted, access_secret FROM messages WHERE id = $1",
+                &[&row_id],
+            )
+        })
+        .await?
+        .into_iter()
+        .next()
+        .ok_or(Error::NotFound)?;
+
+    let until_decrypted: bool = row.get(0);
+    if !access_secret.matches(row.get::<_, Option<String>>(1).as_deref()) {
+        Err(Error::NotFound)
+    } else if until_decrypted {
+        expire(config, &conn, &id).await
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use lazy_static::lazy_static;
+use lettre::{Message, SmtpTransport, Transport};
+use reqwest::blocking::multipart::{Form, Part};
+
+use crate::error::Error;
+
+// Transport used to deliver the emails composed by the backend. Sending is
+// blocking, it only happens on the outbox worker thread.
+pub trait Mailer: Send + Sync {
+    fn send(&self, email: &Message) -> Result<(), Error>;
+}
+
+// Comma separated envelope recipients of an email
+fn recipients(email: &Message) -> String {
+    email
+        .envelope()
+        .to()
+        .iter()
+        .map(|address| address.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl Mailer for SmtpTransport {
+    fn send(&self, email: &Message) -> Result<(), Error> {
+        Transport::send(self, email)?;
+        Ok(())
+    }
+}
+
+// Mail provider with an HTTP API accepting complete MIME messages, like the
+// `messages.mime` endpoint of Mailgun. `url` is the base url for the sending
+// domain, for example `https://api.eu.mailgun.net/v3/tguard.example.com`.
+pub struct HttpMailer {
+    pub url: String,
+    pub key: String,
+}
+
+impl Mailer for HttpMailer {
+    fn send(&self, email: &Message) -> Result<(), Error> {
+        let form = Form::new().text("to", recipients(email)).part(
+            "message",
+            Part::bytes(email.formatted()).file_name("message.mime"),
+        );
+
+        reqwest::blocking::Client::new()
+            .post(format!("{}/messages.mime", self.url))
+            .basic_auth("api", Some(&self.key))
+            .multipart(form)
+            .send()?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+// Writes emails to a maildir instead of sending them, for development and for
+// handing them to another system
+pub struct MaildirMailer {
+    pub directory: PathBuf,
+}
+
+impl Mailer for MaildirMailer {
+    fn send(&self, email: &Message) -> Result<(), Error> {
+        for subdirectory in &["tmp", "new", "cur"] {
+            fs::create_dir_all(self.directory.join(subdirectory))?;
+        }
+
+        // Files are written to `tmp` and then moved to `new`, so that readers
+        // never see a partially written email
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let name = format!(
+            "{}.{}.tguard",
+            time.as_secs(),
+            hex::encode(rand::random::<[u8; 8]>())
+        );
+        let path = self.directory.join("tmp").join(&name);
+        fs::write(&path, email.formatted())?;
+        fs::rename(&path, self.directory.join("new").join(&name))?;
+
+        Ok(())
+    }
+}
+
+lazy_static! {
+    static ref MAILBOX: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
+}
+
+// Keeps emails in memory, shared by all instances in the process, so tests can
+// inspect them without a mail server. Never use it in production, the emails
+// are only discarded when taken.
+pub struct MemoryMailer;
+
+impl MemoryMailer {
+    // Remove and return the emails sent so far, oldest first
+    pub fn take() -> Vec<String> {
+        let mut mailbox = MAILBOX.lock().unwrap();
+        std::mem::take(&mut *mailbox)
+            .into_iter()
+            .map(|email| String::from_utf8_lossy(&email).into_owned())
+            .collect()
+    }
+}
+
+impl Mailer for MemoryMailer {
+    fn send(&self, email: &Message) -> Result<(), Error> {
+        log::info!("Keeping email to {} in memory", recipients(email));
+        MAILBOX.lock().unwrap().push(email.formatted());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{HttpMailer, MaildirMailer, Mailer};
+    use crate::test_util;
+    use lettre::Message;
+
+    fn email() -> Message {
+        Message::builder()
+            .from("from@example.com".parse().unwrap())
+            .to("to@example.com".parse().unwrap())
+            .subject("Example subject")
+            .body("Example body".to_owned())
+            .unwrap()
+    }
+
+    #[test]
+    fn maildir() {
+        let directory = std::env::temp_dir().join(format!(
+            "tguard-maildir-{}",
+            hex::encode(rand::random::<[u8; 8]>())
+        ));
+        let mailer = MaildirMailer {
+            directory: directory.clone(),
+        };
+        mailer.send(&email()).unwrap();
+
+        let files: Vec<_> = std::fs::read_dir(directory.join("new"))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(files.len(), 1);
+        let content = std::fs::read_to_string(files[0].path()).unwrap();
+        assert!(content.contains("Subject: Example subject"));
+        assert_eq!(std::fs::read_dir(directory.join("tmp")).unwrap().count(), 0);
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn http() {
+        let (url, server) = test_util::stand_in("/v3/example.com");
+        let mailer = HttpMailer {
+            url,
+            key: "key".to_owned(),
+        };
+        mailer.send(&email()).unwrap();
+
+        let request = server.join().unwrap();
+        assert!(request.starts_with("post /v3/example.com/messages.mime "));
+        // Basic authentication with user `api` and the key, headers are lowercased
+        let authorization = format!("authorization: basic {}", base64::encode("api:key"));
+        assert!(request.contains(&authorization.to_lowercase()));
+        assert!(request.contains("to@example.com"));
+        assert!(request.contains("Subject: Example subject"));
+    }
+}
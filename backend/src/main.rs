@@ -3,42 +3,65 @@ extern crate rocket;
 extern crate dotenv;
 extern crate rocket_sync_db_pools;
 
+mod access;
+mod api_key;
+mod bounce;
 mod config;
+mod dkim;
 mod email;
 mod error;
+mod events;
 mod id;
+mod limit;
+mod mailer;
+mod manage;
+mod notifier;
+mod outbox;
+#[cfg(feature = "dev-pkg")]
+mod pkg;
+mod ratelimit;
+mod receipt;
 mod receive;
 mod sentry;
 mod sign;
+mod signed_url;
 mod storage;
+mod templates;
+#[cfg(test)]
+mod test_util;
+
+use std::net::IpAddr;
 
-use common::{DownloadResult, MessageData, SealedMessage};
+use common::{
+    AttributeValue, DownloadLimit, DownloadResult, Locale, MessageData, SealedMessage, SendResult,
+    SentMessage,
+};
 use dotenv::dotenv;
 
 use rocket::fairing::AdHoc;
+use rocket::response::Redirect;
 use rocket::serde::json::Json;
-use rocket::State;
+use rocket::{Either, State};
 use rocket_sync_db_pools::{database, postgres};
 use sentry::SentryLogger;
-use serde::{Deserialize, Serialize};
 use validator::Validate;
 
+use crate::access::AccessSecret;
+use crate::api_key::Sender;
 use crate::config::Config;
-use crate::email::send_email;
 use crate::error::Error;
-use crate::id::Id;
+use crate::events::{message_status, record_event, Event};
+use crate::id::{hash_secret, new_access_secret, new_management_token, Id};
+use crate::limit::{decrypted, expire, is_limited, take_download, Access};
+use crate::manage::{disclose, message_owner, removed, withdraw};
+use crate::outbox::{delivery_status, Worker, NOTIFICATION};
+use crate::receipt::{receipt, submit_receipt};
 use crate::receive::new_email;
 #[cfg(debug_assertions)]
 use crate::receive::poll;
 use crate::sentry::SentryFairing;
 use crate::sign::{sign_message, sign_result};
 
-#[derive(Serialize, Deserialize, Debug)]
-struct PostResult {
-    error: bool,
-    id: Option<String>,
-}
-
 fn encode_sealed_message(message: &SealedMessage) -> Result<Vec<u8>, Error> {
     Ok(serde_json::to_vec(message)?)
 }
@@ -56,11 +79,15 @@ fn uses_allowed_attributes(config: &Config, message: &SealedMessage) -> bool {
 async fn api(
     config: &State<Config>,
     conn: Database,
+    sender: Sender,
+    ip: Option<IpAddr>,
     request: Json<MessageData>,
-) -> Result<(), Error> {
+) -> Result<Json<SendResult>, Error> {
+    config.rate_limiter.check_ip(&conn, ip).await?;
     request.validate()?;
 
-    // Check whether attributes are allowed and files are not too big
+    // Check whether attributes are allowed, files are not too big and
+    // recipients can be notified
     for message in &request.recipient_messages {
         if !uses_allowed_attributes(config, &message.sealed) {
             return Err(Error::InvalidAttribute);
@@ -68,44 +95,206 @@ async fn api(
         if message.sealed.ct.len() > config.maximum_file_size {
             return Err(Error::TooBig);
         }
+        // Recipients are only notified through channels that are configured
+        if !request.share_link && config.notifier(&message.channel).is_none() {
+            return Err(Error::UnsupportedChannel);
+        }
     }
 
-    for message in &request.recipient_messages {
+    // Systems sending with an API key are restricted further by that key
+    let api_key = match &sender {
+        Sender::Key(key) => {
+            if !key.allows_sender(&request.from) {
+                return Err(Error::InvalidSender);
+            }
+            for message in &request.recipient_messages {
+                for attribute in &message.sealed.attributes {
+                    if !key.allows_attribute(&attribute.identifier.0) {
+                        return Err(Error::InvalidAttribute);
+                    }
+                }
+            }
+            Some(key.clone())
+        }
+        Sender::Anonymous => None,
+    };
+
+    // The copy for the sender is not counted as a recipient
+    let recipients: Vec<&str> = request
+        .recipient_messages
+        .iter()
+        .filter(|message| !message.sender_copy)
+        .map(|message| message.to.as_str())
+        .collect();
+    config
+        .rate_limiter
+        .check_message(&conn, &request.from, &recipients)
+        .await?;
+
+    let encoded_messages = request
+        .recipient_messages
+        .iter()
+        .map(|message| encode_sealed_message(&message.sealed))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Store all messages before adding any rows, so that the submission can be
+    // undone by removing what was stored
+    let mut stored = Vec::new();
+    for encoded_message in encoded_messages {
         let id = Id::new();
-        let row_id = id.to_string();
-        let from = request.from.clone();
-        let subject = request.subject.clone();
-        let to_copy = message.to.clone();
-        let signature = request.signature.clone();
+        if let Err(e) = config.storage.store(encoded_message, &id.to_string()).await {
+            remove_stored(config, &stored, false).await;
+            return Err(e);
+        }
+        stored.push(id);
+    }
 
-        conn.run(move |c| {
-            c.execute(
-                "INSERT INTO messages (id, from_address, to_address, subject, signature) VALUES ($1, $2, $3, $4, $5)",
-                &[&row_id, &from, &to_copy, &subject, &signature],
-            )
-        }).await?;
-
-        let encoded_message = encode_sealed_message(&message.sealed)?;
-        config
-            .storage
-            .store(encoded_message.clone(), &id.to_string())
-            .await?;
-
-        send_email(
-            config,
-            &id,
-            &request.from,
-            &message.to,
-            &request.subject,
-            encoded_message,
-        )?;
+    let locale_for = |to: &str| request.locale.unwrap_or_else(|| Locale::for_address(to));
+    let accepted: Vec<SentMessage> = stored
+        .iter()
+        .zip(&request.recipient_messages)
+        .map(|(id, message)| {
+            let access_secret = new_access_secret();
+            // Shared links are handed over by the sender instead of emailed
+            let url = if request.share_link {
+                let locale = locale_for(&message.to);
+                Some(email::download_url(config, id, Some(&access_secret), locale))
+            } else {
+                None
+            };
+            SentMessage {
+                id: id.to_string(),
+                to: message.to.clone(),
+                management_token: new_management_token(),
+                access_secret,
+                url,
+                sender_copy: message.sender_copy,
+            }
+        })
+        .collect();
+
+    // The access secrets are only kept until the notifications are sent, and
+    // not in the database
+    if !request.share_link {
+        for sent in &accepted {
+            let id = &sent.id;
+            if let Err(e) = outbox::store_access_secret(config, id, &sent.access_secret).await {
+                remove_stored(config, &stored, true).await;
+                return Err(e);
+            }
+        }
+    }
+
+    // All rows are added in a single transaction, notifications are only sent
+    // by the outbox worker once it is committed
+    let rows = accepted
+        .iter()
+        .zip(&request.recipient_messages)
+        .map(|(sent, message)| {
+            Ok(MessageRow {
+                id: sent.id.clone(),
+                to: sent.to.clone(),
+                token_hash: hash_secret(&sent.management_token),
+                access_secret_hash: hash_secret(&sent.access_secret),
+                locale: locale_for(&sent.to).code(),
+                channel: message.channel.code(),
+                sender_copy: message.sender_copy,
+                attributes: serde_json::to_value(&message.sealed.attributes)?,
+                timestamp: message.sealed.timestamp as i64,
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    let from = request.from.clone();
+    let subject = request.subject.clone();
+    let signature = request.signature.clone();
+    let link_only = request.link_only;
+    let share_link = request.share_link;
+    let (downloads_left, until_decrypted) = match request.download_limit {
+        Some(DownloadLimit::Count(count)) => (Some(count as i32), false),
+        Some(DownloadLimit::UntilDecrypted) => (None, true),
+        None => (None, false),
+    };
+    let result = conn
+        .run(move |c| -> Result<(), Error> {
+            let mut transaction = c.transaction()?;
+            // Checked along with adding the rows, so that concurrent submissions
+            // cannot exceed the quota together
+            let api_key = match api_key {
+                Some(key) => {
+                    let count = rows.iter().filter(|row| !row.sender_copy).count();
+                    key.check_quota(&mut transaction, count)?;
+                    Some(key.id)
+                }
+                None => None,
+            };
+            for row in &rows {
+                transaction.execute(
+                    "INSERT INTO messages (id, from_address, to_address, subject, signature, api_key, management_token, downloads_left, until_decrypted, access_secret, locale, link_only, channel, sender_copy, attributes, timestamp) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)",
+                    &[&row.id, &from, &row.to, &subject, &signature, &api_key, &row.token_hash, &downloads_left, &until_decrypted, &row.access_secret_hash, &row.locale, &link_only, &row.channel, &row.sender_copy, &row.attributes, &row.timestamp],
+                )?;
+                if !share_link {
+                    outbox::enqueue(&mut transaction, &row.id, NOTIFICATION)?;
+                }
+            }
+            transaction.commit()?;
+            Ok(())
+        })
+        .await;
+
+    if let Err(e) = result {
+        remove_stored(config, &stored, !share_link).await;
+        return Err(e);
     }
 
-    Ok(())
+    Ok(Json(SendResult { accepted }))
+}
+
+// Message for a single recipient as added to the database. The attributes and
+// timestamp of the sealed message are kept with it, so the download page does
+// not need to read the stored content.
+struct MessageRow {
+    id: String,
+    to: String,
+    token_hash: String,
+    access_secret_hash: String,
+    locale: &'static str,
+    channel: String,
+    sender_copy: bool,
+    attributes: serde_json::Value,
+    timestamp: i64,
+}
+
+// Remove the stored messages of a submission that could not be completed, and
+// the access secrets kept for their notifications
+async fn remove_stored(config: &Config, ids: &[Id], access_secrets: bool) {
+    for id in ids {
+        if let Err(e) = config.storage.delete(&id.to_string()).await {
+            log::error!("Failed to remove stored message {}: {}", id, e);
+        }
+        if access_secrets {
+            outbox::remove_access_secret(config, &id.to_string()).await;
+        }
+    }
 }
 
-#[get("/api/storage/<slug>")]
-async fn serve_storage(config: &State<Config>, slug: String) -> Result<Vec<u8>, Error> {
+// Files in local storage are served through urls signed by `retrieve_url`
+#[get("/api/storage/<slug>?<expires>&<signature>")]
+async fn serve_storage(
+    config: &State<Config>,
+    conn: Database,
+    slug: String,
+    expires: u64,
+    signature: String,
+) -> Result<Vec<u8>, Error> {
+    signed_url::verify(
+        &config.url_signing_key,
+        &format!("/api/storage/{}", slug),
+        expires,
+        &signature,
+    )?;
+    if is_limited(&conn, &slug).await? {
+        return Err(Error::NotFound);
+    }
     config.storage.serve(&slug).await
 }
 
@@ -114,13 +303,24 @@ async fn download(
     config: &State<Config>,
     conn: Database,
     id: Id,
+    access_secret: AccessSecret,
 ) -> Result<Json<DownloadResult>, Error> {
     let id_clone = id.clone();
-    if let Some((from, to, subject, signature)) = conn
+    if let Some((
+        from,
+        to,
+        subject,
+        signature,
+        downloads_left,
+        until_decrypted,
+        secret_hash,
+        attributes,
+        timestamp,
+    )) = conn
         .run(move |c| {
             let result = c
                 .query(
-                    "SELECT from_address, to_address, subject, signature FROM messages WHERE id = $1",
+                    "SELECT from_address, to_address, subject, signature, downloads_left, until_decrypted, access_secret, attributes, timestamp FROM messages WHERE id = $1",
                     &[&id.to_string()],
                 )
                 .unwrap();
@@ -130,24 +330,92 @@ async fn download(
                     row.get::<_, String>(1),
                     row.get::<_, String>(2),
                     row.get::<_, Option<String>>(3),
+                    row.get::<_, Option<i32>>(4),
+                    row.get::<_, bool>(5),
+                    row.get::<_, Option<String>>(6),
+                    row.get::<_, Option<serde_json::Value>>(7),
+                    row.get::<_, Option<i64>>(8),
                 )
             })
         })
         .await
     {
+        // A wrong secret is treated like a message that does not exist
+        if !access_secret.matches(secret_hash.as_deref()) {
+            return Err(Error::NotFound);
+        }
+
+        // The content itself is only read from storage by `download_content`
+        let attributes: Vec<AttributeValue> =
+            serde_json::from_value(attributes.ok_or(Error::MissingData)?)?;
+        let timestamp = timestamp.ok_or(Error::MissingData)?;
+        record_event(&conn, &id_clone, Event::PageFetched).await?;
+
         Ok(Json(DownloadResult {
             id: id_clone.to_string(),
             from,
             to,
             subject,
             signature,
-            content: config.storage.retrieve_url(&id_clone.to_string()).await?,
+            attributes,
+            timestamp: timestamp as u64,
+            content: signed_url::sign(
+                &config.url_signing_key,
+                &format!("/api/download/{}/content", id_clone),
+                CONTENT_URL_VALIDITY,
+            ),
+            download_limit: match (until_decrypted, downloads_left) {
+                (true, _) => Some(DownloadLimit::UntilDecrypted),
+                (false, Some(left)) => Some(DownloadLimit::Count(left as u32)),
+                (false, None) => None,
+            },
         }))
     } else {
-        Err(Error::NotFound)
+        Err(removed(&conn, &id_clone).await?)
     }
 }
 
+// The content is fetched separately from the metadata once the recipient has
+// obtained a key, so that it is only counted when a message is actually read.
+// Content of messages with a download limit is served directly instead of
+// through a storage url, which could be fetched any number of times.
+// The url is signed when the metadata is fetched with the access secret, so
+// the secret itself does not need to be sent along.
+#[get("/api/download/<id>/content?<expires>&<signature>")]
+async fn download_content(
+    config: &State<Config>,
+    conn: Database,
+    id: Id,
+    expires: u64,
+    signature: String,
+) -> Result<Either<Redirect, Vec<u8>>, Error> {
+    signed_url::verify(
+        &config.url_signing_key,
+        &format!("/api/download/{}/content", id),
+        expires,
+        &signature,
+    )?;
+    message_owner(&conn, &id).await?;
+    let access = take_download(&conn, &id).await?;
+    record_event(&conn, &id, Event::ContentFetched).await?;
+
+    match access {
+        Access::Unlimited => Ok(Either::Left(Redirect::to(
+            config.storage.retrieve_url(&id.to_string()).await?,
+        ))),
+        Access::Limited { last } => {
+            let content = config.storage.retrieve(&id.to_string()).await?;
+            if last {
+                expire(config, &conn, &id).await?;
+            }
+            Ok(Either::Right(content))
+        }
+    }
+}
+
+// Seconds during which the url to the content of a message is valid
+const CONTENT_URL_VALIDITY: u64 = 300;
+
 #[database("db")]
 pub struct Database(postgres::Client);
 
@@ -174,39 +442,74 @@ fn setup(rocket: rocket::Rocket<rocket::Build>) -> rocket::Rocket<rocket::Build>
     let rocket = rocket
         .attach(Database::fairing())
         .attach(AdHoc::config::<Config>())
+        .attach(AdHoc::on_liftoff("Outbox worker", |rocket| {
+            Box::pin(async move {
+                if rocket.state::<Config>().map_or(false, |c| c.outbox_worker) {
+                    Worker::spawn(rocket.figment());
+                }
+            })
+        }))
         .mount(
             "/",
             routes![
                 api,
+                decrypted,
+                delivery_status,
+                disclose,
                 download,
+                download_content,
+                message_status,
                 new_email,
+                receipt,
                 serve_storage,
                 sign_message,
-                sign_result
+                sign_result,
+                submit_receipt,
+                withdraw
             ],
         );
 
     #[cfg(debug_assertions)]
     let rocket = rocket.mount("/", routes![poll]);
 
+    #[cfg(feature = "dev-pkg")]
+    let rocket = {
+        log::warn!("Development PKG enabled, keys are issued without IRMA disclosure");
+        rocket.manage(pkg::DevPkg::new()).mount(
+            "/",
+            routes![pkg::parameters, pkg::request, pkg::status, pkg::result],
+        )
+    };
+
     rocket
 }
 
 #[cfg(test)]
 mod test {
-    use super::{rocket, setup, DownloadResult, SealedMessage};
+    use super::{rocket, setup, signed_url, DownloadResult, SealedMessage, Worker};
+    use crate::api_key::hash_api_key;
+    use crate::bounce;
+    use crate::dkim;
+    use crate::id::Id;
+    use crate::mailer::MemoryMailer;
+    use crate::notifier;
+    use crate::test_util;
     use cloud_storage::ListRequest;
     use cloud_storage::Object;
-    use common::{AttributeIdentifier, AttributeValue};
+    use common::{
+        AttributeIdentifier, AttributeValue, DeliveryStatus, DownloadLimit, MessageStatus,
+        SendResult,
+    };
     use figment::providers::Format;
     use figment::providers::Toml;
     use figment::Figment;
-    use rocket::http::ContentType;
+    use rocket::http::{ContentType, Header};
     use rocket::http::Status;
-    use rocket::local::blocking::Client;
-    use serde::Deserialize;
+    use rocket::local::blocking::{Client, LocalRequest};
     use serde_json::json;
     use serial_test::serial;
+    #[cfg(feature = "dev-pkg")]
+    use tguard_client::{Client as TguardClient, Message, Recipient};
 
     #[post("/setup_db")]
     async fn setup_db(conn: super::Database) {
@@ -216,15 +519,17 @@ mod test {
         .await;
     }
 
-    // Clear the mailhog message list
-    fn reset_mailhog(mailhog_host: &str) {
-        let client = reqwest::blocking::Client::new();
-        client
-            .delete(format!("http://{}:1080/api/v1/messages", mailhog_host))
-            .send()
-            .expect("Failed to reset mailhog message list")
-            .error_for_status()
-            .expect("Failed to reset mailhog message list");
+    // Issue the API key `test-key`, for any sender address
+    #[post("/setup_api_key")]
+    async fn setup_api_key(conn: super::Database) {
+        conn.run(|c| {
+            c.execute(
+                "INSERT INTO api_keys (name, key_hash, sender_pattern) VALUES ('test', $1, '*')",
+                &[&hash_api_key("test-key")],
+            )
+            .unwrap();
+        })
+        .await;
     }
 
     // Clear the object bucket
@@ -249,49 +554,42 @@ mod test {
             .expect("Invalid message")
     }
 
-    #[derive(Deserialize)]
-    struct MailhogMessageRaw {
-        #[serde(rename = "Data")]
-        data: String,
-    }
-
-    #[derive(Deserialize)]
-    struct MailhogMessage {
-        #[serde(rename = "Raw")]
-        raw: MailhogMessageRaw,
+    // Wait for the outbox worker to deliver an email, and return the first one
+    // kept by the memory mailer
+    fn receive_email() -> String {
+        let mut emails = Vec::new();
+        for _ in 0..50 {
+            emails = MemoryMailer::take();
+            if !emails.is_empty() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(200));
+        }
+        emails.into_iter().next().expect("No email received")
     }
 
-    // Extract id from the first email in the message list provided by mailhog
-    fn extract_id_from_mailhog(mailhog_host: &str) -> String {
-        let client = reqwest::blocking::Client::new();
-        let messages: Vec<MailhogMessage> = client
-            .get(format!("http://{}:1080/api/v1/messages", mailhog_host))
-            .send()
-            .expect("Failed to fetch messages")
-            .json()
-            .expect("Failed to parse mailhog result");
-        let message = messages.get(0).expect("No email received");
-        let mut parts = message.raw.data.split("https://example.com/download/");
+    // Extract id and access secret from the download link in an email
+    fn extract_id(email: &str) -> (String, String) {
+        let mut parts = email.split("https://example.com/en/download/");
         parts.next().expect("Mail content incomplete");
         let part = parts.next().expect("Mail content incomplete");
         let mut parts = part.split(' ');
-        parts.next().expect("Mail content incomplete").into()
+        let link = parts.next().expect("Mail content incomplete");
+        let (id, secret) = link.split_once('#').expect("Access secret missing");
+        (id.into(), secret.trim().into())
     }
 
-    #[test]
-    #[serial]
-    fn api() {
-        // Setup
-        let mailhog_host = option_env!("MAILHOG_HOST").expect("Missing Mailhog host");
+    fn test_figment() -> Figment {
         let postgres_url = option_env!("TEST_DB").expect("Missing test database");
-        let figment = Figment::from(rocket::config::Config::default())
+        Figment::from(rocket::config::Config::default())
             .select(rocket::Config::DEFAULT_PROFILE)
             .merge(Toml::string(&format!(
                 r#"
 host = "https://example.com"
 mail_user = "test@example.com"
-mail_host = "{}"
-mail_port = 1025
+mail_transport = "memory"
+dkim_selector = "tguard"
+dkim_domain = "example.com"
 mailgun_message_url_prefix = "https://storage.eu.mailgun.net/v3/domains/tweede.golf/messages/"
 from_fallback = "test@example.com"
 storage_type = "gcs"
@@ -300,48 +598,126 @@ allowed_attributes = ["pbdf.sidn-pbdf.email.email"]
 allowed_signing_attributes = ["pbdf.sidn-pbdf.email.email"]
 irmaserver = "http://127.0.0.1:8088"
 maximum_file_size = 32767
+outbox_worker = false
+url_signing_key = "test"
 
 [databases]
 db = {{ url = "{}" }}
                 "#,
-                mailhog_host, postgres_url
-            )));
-        let client = Client::tracked(setup(rocket::custom(figment)).mount("/", routes![setup_db]))
-            .expect("valid rocket instance");
+                postgres_url
+            )))
+            .merge(("dkim_private_key", dkim::test::KEY))
+    }
+
+    // Backend with the given configuration, on an empty database, bucket and
+    // mailbox
+    fn test_client(figment: Figment) -> Client {
+        let client = Client::tracked(
+            setup(rocket::custom(figment)).mount("/", routes![setup_db, setup_api_key]),
+        )
+        .expect("valid rocket instance");
         assert_eq!(client.post("/setup_db").dispatch().status(), Status::Ok);
-        reset_mailhog(mailhog_host);
+        MemoryMailer::take();
         reset_bucket();
+        client
+    }
+
+    const EMAIL: &str = "pbdf.sidn-pbdf.email.email";
 
-        let response = client.post("/api")
+    // Sealed message as sent by the website, for a recipient identified by a
+    // single attribute
+    fn sealed_message(identifier: &str, value: &str) -> serde_json::Value {
+        json!({
+            "c_key": "h9J6WdqlnSgHEULkJbDJ1zBKjJ+LAWaTqEwlAUG5gA9GHT0S3I+0emOES7nfdzpOCEGqbfdDffMEFwqEiW7wGyR3NZJxSmM3GYwTJdZqNbTHosucrw+MsYctOdWdXHS9rfdQBtvlqUE1xYbCnrjsN4RHMpyUj2H+yHit70d0re5CIxUp0yArdidBz6LjUPpd",
+            "ct": "gAMMKLikymhNIDeqUjqjJqEFTj8qWnrUUUhwCrIG6sOplxR4pFnUKA==",
+            "iv": "0z6La7O6CfxcvND0LqDQBA==",
+            "timestamp": 1629883307061_u64,
+            "attributes": [
+                {
+                    "identifier": AttributeIdentifier(identifier.to_owned()),
+                    "value": value,
+                },
+            ],
+        })
+    }
+
+    // Message for a recipient identified by their email address
+    fn recipient(to: &str) -> serde_json::Value {
+        json!({ "to": to, "sealed": sealed_message(EMAIL, to) })
+    }
+
+    // Submission of a message from from@example.com to to@example.com, with
+    // the given fields added or replaced
+    fn send<'c>(client: &'c Client, fields: serde_json::Value) -> LocalRequest<'c> {
+        let mut submission = json!({
+            "from": "from@example.com",
+            "subject": "Example subject",
+            "recipient_messages": [recipient("to@example.com")],
+        });
+        if let Some(fields) = fields.as_object() {
+            for (name, value) in fields {
+                submission[name] = value.clone();
+            }
+        }
+        client
+            .post("/api")
             .header(ContentType::JSON)
-            .body(json!({
-                "from": "from@example.com",
-                "subject": "Example subject",
-                "recipient_messages": [
-                    {
-                        "to": "to@example.com",
-                        "sealed": {
-                            "c_key": "h9J6WdqlnSgHEULkJbDJ1zBKjJ+LAWaTqEwlAUG5gA9GHT0S3I+0emOES7nfdzpOCEGqbfdDffMEFwqEiW7wGyR3NZJxSmM3GYwTJdZqNbTHosucrw+MsYctOdWdXHS9rfdQBtvlqUE1xYbCnrjsN4RHMpyUj2H+yHit70d0re5CIxUp0yArdidBz6LjUPpd",
-                            "ct": "gAMMKLikymhNIDeqUjqjJqEFTj8qWnrUUUhwCrIG6sOplxR4pFnUKA==",
-                            "iv": "0z6La7O6CfxcvND0LqDQBA==",
-                            "timestamp": 1629883307061_u64,
-                            "attributes": [
-                                {
-                                    "identifier": AttributeIdentifier("pbdf.sidn-pbdf.email.email".to_owned()),
-                                    "value": "to@example.com",
-                                },
-                            ],
-                        }
-                    },
-                ],
-            }).to_string())
-            .dispatch();
+            .body(submission.to_string())
+    }
+
+    #[test]
+    #[serial]
+    fn api() {
+        let client = test_client(test_figment());
+        let response = send(&client, json!({})).dispatch();
 
         assert_eq!(response.status(), Status::Ok);
+        let result: SendResult = response.into_json().unwrap();
+        assert_eq!(result.accepted.len(), 1);
+        let sent = &result.accepted[0];
+        assert_eq!(sent.to, "to@example.com");
+        assert_eq!(sent.management_token.len(), 48);
 
-        let id = extract_id_from_mailhog(mailhog_host);
+        // Deliver the notification
+        let mut worker = Worker::new(client.rocket().figment()).expect("valid outbox worker");
+        assert_eq!(worker.process_due().expect("Failed to process outbox"), 1);
 
-        let response = client.get(format!("/api/download/{}", &id)).dispatch();
+        let email = receive_email();
+        assert!(email.contains("To: to@example.com"));
+        assert!(email.contains("Reply-To: from@example.com"));
+        assert!(email.contains("Subject: Example subject"));
+        assert_eq!(dkim::test::verify(&email, dkim::test::KEY), Ok(()));
+        let (id, secret) = extract_id(&email);
+        assert_eq!(id, sent.id);
+        assert_eq!(secret, sent.access_secret);
+
+        // Only the sender can see the delivery status
+        let response = client.get(format!("/api/delivery/{}", &id)).dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        let response = client
+            .get(format!("/api/delivery/{}", &id))
+            .header(Header::new("X-Management-Token", "invalid"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        let response = client
+            .get(format!("/api/delivery/{}", &id))
+            .header(Header::new("X-Management-Token", sent.management_token.clone()))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let status: DeliveryStatus = response.into_json().unwrap();
+        assert_eq!(status.status, "sent");
+
+        // The download link only works with the access secret
+        let response = client
+            .get(format!("/api/download/{}", &id))
+            .header(Header::new("X-Access-Secret", "invalid"))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+
+        let response = client
+            .get(format!("/api/download/{}", &id))
+            .header(Header::new("X-Access-Secret", secret.clone()))
+            .dispatch();
 
         assert_eq!(response.status(), Status::Ok);
 
@@ -349,17 +725,34 @@ db = {{ url = "{}" }}
         let result: DownloadResult = serde_json::from_str(&response_body).unwrap();
 
         let expected = DownloadResult {
-            id,
+            id: id.clone(),
             to: "to@example.com".to_string(),
             from: "from@example.com".to_string(),
             subject: "Example subject".to_string(),
             signature: None,
+            attributes: vec![AttributeValue {
+                identifier: AttributeIdentifier("pbdf.sidn-pbdf.email.email".to_owned()),
+                value: "to@example.com".into(),
+            }],
+            timestamp: 1629883307061,
             content: result.content.clone(),
+            download_limit: None,
         };
 
         assert_eq!(result, expected);
 
-        let message = fetch_object(result.content);
+        // The content url is signed, and cannot be altered
+        let content_path = format!("/api/download/{}/content", &id);
+        assert!(result.content.starts_with(&format!("{}?expires=", content_path)));
+        let response = client
+            .get(format!("{}?expires=9999999999&signature=00", content_path))
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+
+        let response = client.get(result.content).dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        let location = response.headers().get_one("Location").unwrap().to_owned();
+        let message = fetch_object(location);
 
         let expected_message = SealedMessage {
             c_key: "h9J6WdqlnSgHEULkJbDJ1zBKjJ+LAWaTqEwlAUG5gA9GHT0S3I+0emOES7nfdzpOCEGqbfdDffMEFwqEiW7wGyR3NZJxSmM3GYwTJdZqNbTHosucrw+MsYctOdWdXHS9rfdQBtvlqUE1xYbCnrjsN4RHMpyUj2H+yHit70d0re5CIxUp0yArdidBz6LjUPpd".to_string(),
@@ -373,5 +766,491 @@ db = {{ url = "{}" }}
         };
 
         assert_eq!(message, expected_message);
+
+        // Check the events recorded for the sender
+        let response = client
+            .get(format!("/api/message/{}/status", &id))
+            .header(Header::new("X-Management-Token", sent.management_token.clone()))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let status: MessageStatus = response.into_json().unwrap();
+        let events: Vec<&str> = status.events.iter().map(|e| e.event.as_str()).collect();
+        assert_eq!(
+            events,
+            vec!["notification_sent", "page_fetched", "content_fetched"]
+        );
+
+        // No receipt was signed, and receipts signed long ago are rejected
+        let response = client
+            .get(format!("/api/message/{}/receipt", &id))
+            .header(Header::new("X-Management-Token", sent.management_token.clone()))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+
+        // Whether a message exists is only told to its sender
+        let response = client
+            .get(format!("/api/message/{}/receipt", &id))
+            .header(Header::new("X-Management-Token", "invalid"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        let response = client
+            .get(format!("/api/message/{}/receipt", Id::new()))
+            .header(Header::new("X-Management-Token", sent.management_token.clone()))
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+
+        let response = client
+            .post(format!("/api/message/{}/receipt?session=invalid&hash=00&time=0", &id))
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+
+        // Withdraw the message
+        let response = client
+            .delete(format!("/api/message/{}", &id))
+            .header(Header::new("X-Management-Token", "invalid"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+
+        // Only sessions started with `/api/disclose` are accepted
+        let response = client
+            .delete(format!("/api/message/{}", &id))
+            .header(Header::new("X-Irma-Session", "unknown"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+
+        let response = client
+            .delete(format!("/api/message/{}", &id))
+            .header(Header::new("X-Management-Token", sent.management_token.clone()))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let response = client
+            .get(format!("/api/download/{}", &id))
+            .header(Header::new("X-Access-Secret", secret))
+            .dispatch();
+        assert_eq!(response.status(), Status::Gone);
+        assert_ne!(response.into_string().unwrap(), common::EXHAUSTED);
+    }
+
+    #[test]
+    #[serial]
+    fn too_large() {
+        // Clients recognize the body to tell the sender the message is too large
+        let client = test_client(test_figment().merge(("maximum_file_size", 16)));
+        let response = send(&client, json!({})).dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        assert_eq!(response.into_string().unwrap(), common::TOO_LARGE);
+    }
+
+    #[test]
+    #[serial]
+    fn download_limit() {
+        let client = test_client(test_figment());
+        let send_limited = |limit: serde_json::Value| -> (String, String) {
+            let response = send(&client, json!({ "download_limit": limit })).dispatch();
+            assert_eq!(response.status(), Status::Ok);
+            let sent = response.into_json::<SendResult>().unwrap().accepted.remove(0);
+            (sent.id, sent.access_secret)
+        };
+        let metadata = |id: &str, secret: &str| {
+            client
+                .get(format!("/api/download/{}", id))
+                .header(Header::new("X-Access-Secret", secret.to_owned()))
+                .dispatch()
+        };
+
+        // Page views do not count, only fetching the content
+        let (id, secret) = send_limited(json!({ "count": 2 }));
+        let mut content = String::new();
+        for _ in 0..3 {
+            let response = metadata(&id, &secret);
+            assert_eq!(response.status(), Status::Ok);
+            content = response.into_json::<DownloadResult>().unwrap().content;
+        }
+        let storage_url = signed_url::sign(b"test", &format!("/api/storage/{}", &id), 30);
+        let response = client.get(storage_url).dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        for _ in 0..2 {
+            let response = client.get(content.clone()).dispatch();
+            assert_eq!(response.status(), Status::Ok);
+            let message: SealedMessage = response.into_json().unwrap();
+            assert_eq!(message.iv, "0z6La7O6CfxcvND0LqDQBA==");
+        }
+        let response = client.get(content).dispatch();
+        assert_eq!(response.status(), Status::Gone);
+        // The recipient is told the limit ran out, not that it was withdrawn
+        let response = metadata(&id, &secret);
+        assert_eq!(response.status(), Status::Gone);
+        assert_eq!(response.into_string().unwrap(), common::EXHAUSTED);
+
+        // Messages limited until decrypted are deleted once the recipient is done
+        let (id, secret) = send_limited(json!("until_decrypted"));
+        let result: DownloadResult = metadata(&id, &secret).into_json().unwrap();
+        assert_eq!(result.download_limit, Some(DownloadLimit::UntilDecrypted));
+        for _ in 0..2 {
+            let response = client.get(result.content.clone()).dispatch();
+            assert_eq!(response.status(), Status::Ok);
+        }
+        let response = client
+            .post(format!("/api/download/{}/decrypted", &id))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        let response = client
+            .post(format!("/api/download/{}/decrypted", &id))
+            .header(Header::new("X-Access-Secret", secret.clone()))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(metadata(&id, &secret).status(), Status::Gone);
+
+        // A limit of zero downloads is rejected
+        let response = send(&client, json!({ "download_limit": { "count": 0 } })).dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[test]
+    #[serial]
+    fn link_only() {
+        let client = test_client(test_figment().merge(("maximum_attachment_size", 1000)));
+        let mut worker = Worker::new(client.rocket().figment()).expect("valid outbox worker");
+        let mut send_email = |ct: &str, link_only: bool| -> String {
+            let mut sealed = sealed_message(EMAIL, "to@example.com");
+            sealed["ct"] = json!(ct);
+            let response = send(
+                &client,
+                json!({
+                    "recipient_messages": [{ "to": "to@example.com", "sealed": sealed }],
+                    "link_only": link_only,
+                }),
+            )
+            .dispatch();
+            assert_eq!(response.status(), Status::Ok);
+            assert_eq!(worker.process_due().expect("Failed to process outbox"), 1);
+            receive_email()
+        };
+
+        // The encrypted message is attached, unless the sender asks not to,
+        // and the email says so
+        let small = "gAMMKLikymhNIDeqUjqjJqEFTj8qWnrUUUhwCrIG6sOplxR4pFnUKA==";
+        let attached = "The encrypted message is also attached to this email.";
+        let email = send_email(small, false);
+        assert!(email.contains("encrypted.irmaseal"));
+        assert!(email.contains(attached));
+        let email = send_email(small, true);
+        assert!(!email.contains("encrypted.irmaseal"));
+        assert!(!email.contains(attached));
+        assert!(email.contains("https://example.com/en/download/"));
+
+        // Messages larger than the maximum attachment size are only linked to
+        let large = base64::encode(vec![0; 1000]);
+        let email = send_email(&large, false);
+        assert!(!email.contains("encrypted.irmaseal"));
+        assert!(email.contains("https://example.com/en/download/"));
+    }
+
+    #[test]
+    #[serial]
+    fn share_link() {
+        let client = test_client(test_figment());
+        let response = send(
+            &client,
+            json!({
+                "recipient_messages": [recipient("to@example.nl")],
+                "share_link": true,
+            }),
+        )
+        .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let sent = response.into_json::<SendResult>().unwrap().accepted.remove(0);
+
+        // The download link is returned, in the language of the recipient
+        let url = sent.url.expect("Download link missing");
+        assert_eq!(
+            url,
+            format!("https://example.com/nl/download/{}#{}", sent.id, sent.access_secret)
+        );
+        let response = client
+            .get(format!("/api/download/{}", sent.id))
+            .header(Header::new("X-Access-Secret", sent.access_secret))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        // Nobody is notified
+        let mut worker = Worker::new(client.rocket().figment()).expect("valid outbox worker");
+        assert_eq!(worker.process_due().expect("Failed to process outbox"), 0);
+        assert!(MemoryMailer::take().is_empty());
+    }
+
+    #[test]
+    #[serial]
+    fn sender_copy() {
+        // The copy does not count towards the rate limit of the sender
+        let client = test_client(test_figment().merge((
+            "rate_limit.per_sender",
+            json!({ "capacity": 1, "per_hour": 1 }),
+        )));
+        let copy = |to: &str| {
+            let mut copy = recipient(to);
+            copy["sender_copy"] = json!(true);
+            copy
+        };
+        let send_to = |recipient_messages: serde_json::Value| {
+            send(&client, json!({ "recipient_messages": recipient_messages })).dispatch()
+        };
+
+        // The copy can only be sent to the sender, along with the message for
+        // another recipient
+        let response = send_to(json!([recipient("to@example.com"), copy("other@example.com")]));
+        assert_eq!(response.status(), Status::BadRequest);
+        let response = send_to(json!([copy("from@example.com")]));
+        assert_eq!(response.status(), Status::BadRequest);
+
+        let response = send_to(json!([recipient("to@example.com"), copy("from@example.com")]));
+        assert_eq!(response.status(), Status::Ok);
+        let result: SendResult = response.into_json().unwrap();
+        assert_eq!(result.accepted.len(), 2);
+        assert!(!result.accepted[0].sender_copy);
+        let copy = &result.accepted[1];
+        assert!(copy.sender_copy);
+        assert_eq!(copy.to, "from@example.com");
+
+        // The sender is notified of the copy like any recipient, and can
+        // download it
+        let mut worker = Worker::new(client.rocket().figment()).expect("valid outbox worker");
+        assert_eq!(worker.process_due().expect("Failed to process outbox"), 2);
+        let emails = MemoryMailer::take();
+        assert!(emails
+            .iter()
+            .any(|email| email.contains("To: from@example.com") && email.contains(&copy.id)));
+        let response = client
+            .get(format!("/api/download/{}", copy.id))
+            .header(Header::new("X-Access-Secret", copy.access_secret.clone()))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[test]
+    #[serial]
+    fn notification_channels() {
+        let (sms_url, sms_gateway) = test_util::stand_in("/notify");
+        let (webhook_url, webhook) = test_util::stand_in("/notify");
+        let client = test_client(
+            test_figment()
+                .merge((
+                    "allowed_attributes",
+                    [EMAIL, "pbdf.sidn-pbdf.mobilenumber.mobilenumber"],
+                ))
+                .merge(("sms_gateway_url", sms_url))
+                .merge(("sms_gateway_key", "key"))
+                .merge(("sms_sender", "TGuard"))
+                .merge(("webhooks.chat", json!({ "url": webhook_url, "secret": "secret" }))),
+        );
+        let send_to = |to: &str, channel: serde_json::Value, attribute: (&str, &str)| -> Status {
+            let message = json!({
+                "to": to,
+                "channel": channel,
+                "sealed": sealed_message(attribute.0, attribute.1),
+            });
+            send(&client, json!({ "recipient_messages": [message] }))
+                .dispatch()
+                .status()
+        };
+        let mut worker = Worker::new(client.rocket().figment()).expect("valid outbox worker");
+
+        // Recipients can only be notified through channels that are configured,
+        // at an address that fits the channel
+        let phone = ("pbdf.sidn-pbdf.mobilenumber.mobilenumber", "+31612345678");
+        assert_eq!(send_to("0612345678", json!("sms"), phone), Status::BadRequest);
+        let email = (EMAIL, "to@example.com");
+        let unknown = json!({ "webhook": "unknown" });
+        assert_eq!(send_to("@to", unknown, email), Status::BadRequest);
+
+        // A message for a phone number is announced by SMS through the gateway
+        assert_eq!(send_to("+31612345678", json!("sms"), phone), Status::Ok);
+        assert_eq!(worker.process_due().expect("Failed to process outbox"), 1);
+        let request = sms_gateway.join().unwrap();
+        assert!(request.starts_with("post /notify "));
+        assert!(request.contains("authorization: bearer key"));
+        assert!(request.contains(r#""from":"TGuard""#));
+        assert!(request.contains(r#""to":"+31612345678""#));
+        assert!(request.contains("https://example.com/nl/download/"));
+
+        // Webhooks receive the notification as JSON, signed with their secret
+        let chat = json!({ "webhook": "chat" });
+        assert_eq!(send_to("@to", chat, email), Status::Ok);
+        assert_eq!(worker.process_due().expect("Failed to process outbox"), 1);
+        let request = webhook.join().unwrap();
+        let (head, body) = request.split_once("\r\n\r\n").unwrap();
+        let signature = notifier::signature("secret", body.as_bytes());
+        assert!(head.contains(&format!("x-tguard-signature: {}", signature)));
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["to"], "@to");
+        assert_eq!(body["subject"], "Example subject");
+        assert!(body["url"]
+            .as_str()
+            .unwrap()
+            .starts_with("https://example.com/en/download/"));
+        assert!(body["text"].as_str().unwrap().contains("from@example.com"));
+
+        // Neither is sent by email
+        assert!(MemoryMailer::take().is_empty());
+    }
+
+    #[test]
+    #[serial]
+    fn bounce() {
+        let client = test_client(test_figment());
+        assert_eq!(client.post("/setup_api_key").dispatch().status(), Status::Ok);
+        let mut worker = Worker::new(client.rocket().figment()).expect("valid outbox worker");
+
+        // Only senders using an API key are emailed about bounces
+        for &authorization in &[None, Some("Bearer test-key")] {
+            let mut request = send(&client, json!({}));
+            if let Some(authorization) = authorization {
+                request = request.header(Header::new("Authorization", authorization));
+            }
+            let response = request.dispatch();
+            assert_eq!(response.status(), Status::Ok);
+            let result: SendResult = response.into_json().unwrap();
+            let sent = &result.accepted[0];
+
+            // The notification carries a Message-ID from which the message is found
+            assert_eq!(worker.process_due().expect("Failed to process outbox"), 1);
+            let email = receive_email();
+            assert!(email.contains(&format!("Message-ID: <{}@example.com>", sent.id)));
+
+            // Mailgun stores the bounce, and is asked for its headers and source
+            let report = bounce::test::report(&sent.id, "to@example.com");
+            let (url, mailgun) = test_util::serve("/notify", vec![
+                json!({
+                    "sender": "MAILER-DAEMON@mx.example.net",
+                    "subject": "Undelivered Mail Returned to Sender",
+                    "body-plain": "Your message could not be delivered.",
+                    "message-headers": [
+                        ["Content-Type", "multipart/report; report-type=delivery-status; boundary=\"boundary\""],
+                    ],
+                })
+                .to_string(),
+                json!({ "body-mime": report }).to_string(),
+            ]);
+            let figment = client
+                .rocket()
+                .figment()
+                .clone()
+                .merge(("mailgun_message_url_prefix", &url));
+            let mailgun_client =
+                Client::tracked(setup(rocket::custom(figment))).expect("valid rocket instance");
+            let response = mailgun_client
+                .post("/api/newemail")
+                .header(ContentType::Form)
+                .body(format!("message-url={}", url))
+                .dispatch();
+            assert_eq!(response.status(), Status::Ok);
+            let requests = mailgun.join().unwrap();
+            assert!(requests[1].contains("accept: message/rfc822"));
+
+            // The notification is marked as bounced
+            let response = client
+                .get(format!("/api/delivery/{}", sent.id))
+                .header(Header::new("X-Management-Token", sent.management_token.clone()))
+                .dispatch();
+            let status: DeliveryStatus = response.into_json().unwrap();
+            assert_eq!(status.status, "bounced");
+            let response = client
+                .get(format!("/api/message/{}/status", sent.id))
+                .header(Header::new("X-Management-Token", sent.management_token.clone()))
+                .dispatch();
+            let status: MessageStatus = response.into_json().unwrap();
+            assert!(status
+                .events
+                .iter()
+                .any(|event| event.event == "notification_bounced"));
+
+            // And the sender is told which recipient was not reached, when the
+            // API key vouches for the sender address
+            if authorization.is_none() {
+                assert_eq!(worker.process_due().expect("Failed to process outbox"), 0);
+                assert!(MemoryMailer::take().is_empty());
+                continue;
+            }
+            assert_eq!(worker.process_due().expect("Failed to process outbox"), 1);
+            let email = receive_email();
+            assert!(email.contains("To: from@example.com"));
+            assert!(email.contains("to@example.com"));
+            assert!(email.contains("Undeliverable: Example subject"));
+        }
+    }
+
+    #[cfg(feature = "dev-pkg")]
+    #[rocket::async_test]
+    #[serial]
+    async fn client() {
+        use common::ibs::unseal;
+        use rocket::tokio::task::spawn_blocking;
+        use rocket::tokio::time::{sleep, Duration};
+
+        // Run the backend in-process, with the development PKG
+        let figment = test_figment()
+            .merge(("port", 8123))
+            .merge(("outbox_worker", true));
+        let rocket = setup(rocket::custom(figment))
+            .mount("/", routes![setup_db])
+            .ignite()
+            .await
+            .expect("valid rocket instance");
+        let shutdown = rocket.shutdown();
+        rocket::tokio::spawn(rocket.launch());
+
+        let server = "http://127.0.0.1:8123";
+        let http = reqwest::Client::new();
+        let mut ready = false;
+        for _ in 0..50 {
+            if let Ok(response) = http.post(format!("{}/setup_db", server)).send().await {
+                assert_eq!(response.status(), reqwest::StatusCode::OK);
+                ready = true;
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert!(ready, "backend did not start");
+        MemoryMailer::take();
+        spawn_blocking(reset_bucket).await.unwrap();
+
+        let client = TguardClient::new(server).pkg(&format!("{}/pkg", server));
+        let message = Message::builder("from@example.com", "Example subject")
+            .text("This is a test message")
+            .recipient(Recipient::email("to@example.com"))
+            .build();
+        let result = client.send(&message).await.expect("Failed to send message");
+        assert_eq!(result.accepted[0].to, "to@example.com");
+
+        let email = spawn_blocking(receive_email).await.unwrap();
+        let (id, secret) = extract_id(&email);
+        let metadata = client
+            .download(&id, Some(&secret))
+            .await
+            .expect("Failed to download");
+
+        assert_eq!(metadata.from, "from@example.com");
+        assert_eq!(metadata.to, "to@example.com");
+        assert_eq!(metadata.subject, "Example subject");
+
+        let sealed = client
+            .fetch_sealed(&metadata.content)
+            .await
+            .expect("Failed to retrieve object");
+        let session = client
+            .start_key_session(&sealed.attributes[0])
+            .await
+            .expect("Failed to start key session");
+        let usk = client
+            .session_key(&session.token, sealed.timestamp)
+            .await
+            .expect("Failed to retrieve key")
+            .expect("Key session not done");
+        let plain = String::from_utf8(unseal(&sealed, &usk).unwrap()).unwrap();
+
+        assert!(plain.contains("This is a test message"));
+
+        shutdown.notify();
     }
 }
@@ -0,0 +1,203 @@
+use std::net::IpAddr;
+
+use irma::{AttributeRequest, DisclosureRequestBuilder, ProofStatus, SessionData, SessionToken};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::{serde::json::Json, State};
+
+use crate::id::{secret_matches, Id};
+use crate::outbox::{remove_access_secret, BOUNCE};
+use crate::{config::Config, error::Error, Database};
+
+pub const EMAIL_ATTRIBUTE: &str = "pbdf.sidn-pbdf.email.email";
+
+// Proof that the requester may manage a message: the management token returned
+// when it was sent, or an IRMA session in which the sender's email was disclosed
+pub enum Authorization {
+    Token(String),
+    IrmaSession(String),
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Authorization {
+    type Error = Error;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let headers = request.headers();
+        if let Some(token) = headers.get_one("X-Management-Token") {
+            Outcome::Success(Authorization::Token(token.to_owned()))
+        } else if let Some(session) = headers.get_one("X-Irma-Session") {
+            Outcome::Success(Authorization::IrmaSession(session.to_owned()))
+        } else {
+            Outcome::Failure((Status::Unauthorized, Error::Unauthorized))
+        }
+    }
+}
+
+// Email address disclosed in a completed IRMA session started by `disclose`
+async fn disclosed_email(config: &Config, conn: &Database, session: &str) -> Result<String, Error> {
+    let token = session.to_owned();
+    let started = conn
+        .run(move |c| {
+            c.query(
+                "SELECT 1 FROM disclose_sessions WHERE token = $1 AND created > now() - interval '1 hour'",
+                &[&token],
+            )
+        })
+        .await?;
+    if started.is_empty() {
+        return Err(Error::Unauthorized);
+    }
+
+    let result = config
+        .irmaserver
+        .result(&SessionToken(session.to_owned()))
+        .await?;
+    if result.proof_status != Some(ProofStatus::Valid) {
+        return Err(Error::Unauthorized);
+    }
+
+    result
+        .disclosed
+        .into_iter()
+        .flatten()
+        .find(|attr| attr.identifier == EMAIL_ATTRIBUTE)
+        .and_then(|attr| attr.raw_value)
+        .ok_or(Error::Unauthorized)
+}
+
+impl Authorization {
+    // Check whether this authorizes managing a message with the given token
+    // hash, sent from the given address
+    pub async fn check(
+        &self,
+        config: &Config,
+        conn: &Database,
+        token_hash: Option<&str>,
+        from: &str,
+    ) -> Result<(), Error> {
+        let authorized = match self {
+            Authorization::Token(token) => {
+                token_hash.map_or(false, |hash| secret_matches(token, hash))
+            }
+            Authorization::IrmaSession(session) => disclosed_email(config, conn, session)
+                .await?
+                .eq_ignore_ascii_case(from),
+        };
+
+        if authorized {
+            Ok(())
+        } else {
+            Err(Error::Unauthorized)
+        }
+    }
+}
+
+// Sender and management token hash of a message
+pub async fn message_owner(conn: &Database, id: &Id) -> Result<(String, Option<String>), Error> {
+    let row_id = id.to_string();
+    let owner = conn
+        .run(move |c| {
+            c.query(
+                "SELECT from_address, management_token FROM messages WHERE id = $1",
+                &[&row_id],
+            )
+        })
+        .await?
+        .get(0)
+        .map(|row| (row.get(0), row.get(1)));
+
+    match owner {
+        Some(owner) => Ok(owner),
+        None => Err(removed(conn, id).await?),
+    }
+}
+
+// Error for a message that does not exist, telling whether it was withdrawn
+// or its download limit ran out
+pub async fn removed(conn: &Database, id: &Id) -> Result<Error, Error> {
+    let id = id.to_string();
+    let reason = conn
+        .run(move |c| {
+            c.query(
+                "SELECT 'withdrawn' FROM withdrawn WHERE id = $1 UNION ALL SELECT 'exhausted' FROM exhausted WHERE id = $1",
+                &[&id],
+            )
+        })
+        .await?
+        .get(0)
+        .map(|row| row.get::<_, String>(0));
+
+    Ok(match reason.as_deref() {
+        Some("withdrawn") => Error::Withdrawn,
+        Some(_) => Error::Exhausted,
+        None => Error::NotFound,
+    })
+}
+
+// Start an IRMA session in which the sender discloses their email address, to
+// manage their messages without a management token
+#[post("/api/disclose")]
+pub async fn disclose(
+    config: &State<Config>,
+    conn: Database,
+    ip: Option<IpAddr>,
+) -> Result<Json<SessionData>, Error> {
+    config.rate_limiter.check_ip(&conn, ip).await?;
+
+    let request = DisclosureRequestBuilder::new()
+        .add_discon(vec![vec![AttributeRequest::Simple(EMAIL_ATTRIBUTE.to_owned())]])
+        .build();
+    let session = config.irmaserver.request(&request).await?;
+
+    // Other sessions disclosing an email address, like those for signing,
+    // do not authorize managing messages
+    let token = session.token.0.clone();
+    conn.run(move |c| -> Result<(), Error> {
+        c.execute(
+            "DELETE FROM disclose_sessions WHERE created < now() - interval '1 hour'",
+            &[],
+        )?;
+        c.execute("INSERT INTO disclose_sessions (token) VALUES ($1)", &[&token])?;
+        Ok(())
+    })
+    .await?;
+
+    Ok(Json(session))
+}
+
+// Withdraw a message, it can no longer be downloaded afterwards
+#[delete("/api/message/<id>")]
+pub async fn withdraw(
+    config: &State<Config>,
+    conn: Database,
+    id: Id,
+    authorization: Authorization,
+) -> Result<(), Error> {
+    let (from, token_hash) = message_owner(&conn, &id).await?;
+    authorization.check(config, &conn, token_hash.as_deref(), &from).await?;
+
+    // A tombstone is kept, so downloads can tell the message was withdrawn
+    let row_id = id.to_string();
+    let pending = conn
+        .run(move |c| -> Result<bool, Error> {
+            let mut transaction = c.transaction()?;
+            let pending = !transaction
+                .query(
+                    "SELECT 1 FROM outbox WHERE message = $1 AND status = 'pending' AND kind != $2",
+                    &[&row_id, &BOUNCE],
+                )?
+                .is_empty();
+            transaction.execute("DELETE FROM messages WHERE id = $1", &[&row_id])?;
+            transaction.execute("INSERT INTO withdrawn (id) VALUES ($1)", &[&row_id])?;
+            transaction.commit()?;
+            Ok(pending)
+        })
+        .await?;
+
+    // The notification will not be sent anymore, nor need its access secret
+    if pending {
+        remove_access_secret(config, &id.to_string()).await;
+    }
+    config.storage.delete(&id.to_string()).await
+}
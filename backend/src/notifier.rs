@@ -0,0 +1,166 @@
+use hmac::{Hmac, Mac, NewMac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tera::Context;
+
+use common::Locale;
+
+use crate::config::Config;
+use crate::email::{download_url, send_email};
+use crate::error::Error;
+use crate::id::Id;
+
+// New message for a single recipient, to be announced through its channel
+pub struct Notification<'a> {
+    pub id: &'a Id,
+    pub access_secret: Option<&'a str>,
+    pub locale: Locale,
+    pub from: &'a str,
+    pub to: &'a str,
+    pub subject: &'a str,
+    // Encrypted message, only attached to emails
+    pub body: Option<Vec<u8>>,
+}
+
+impl Notification<'_> {
+    fn url(&self, config: &Config) -> String {
+        download_url(config, self.id, self.access_secret, self.locale)
+    }
+
+    // Text for channels without room for the full email
+    fn short_message(&self, config: &Config) -> Result<String, Error> {
+        let mut context = Context::new();
+        context.insert("from", self.from);
+        context.insert("subject", self.subject);
+        context.insert("url", &self.url(config));
+        let text = config
+            .templates
+            .render(self.locale, "short_message.txt", &context)?;
+        Ok(text.trim().to_owned())
+    }
+}
+
+// Announces new messages to recipients. Like mail transports, notifiers are
+// blocking and only used on the outbox worker thread.
+pub trait Notifier: Send + Sync {
+    fn notify(&self, config: &Config, notification: Notification) -> Result<(), Error>;
+}
+
+pub struct EmailNotifier;
+
+impl Notifier for EmailNotifier {
+    fn notify(&self, config: &Config, notification: Notification) -> Result<(), Error> {
+        send_email(
+            config,
+            notification.id,
+            notification.access_secret,
+            notification.locale,
+            notification.from,
+            notification.to,
+            notification.subject,
+            notification.body,
+        )
+    }
+}
+
+#[derive(Serialize)]
+struct SmsRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    text: &'a str,
+}
+
+// Gateway sending text messages, which accepts a JSON object with `from`, `to`
+// and `text` posted to `url`, authorized with the key as bearer token
+pub struct SmsGateway {
+    pub url: String,
+    pub key: String,
+    pub sender: String,
+}
+
+impl Notifier for SmsGateway {
+    fn notify(&self, config: &Config, notification: Notification) -> Result<(), Error> {
+        let text = notification.short_message(config)?;
+        reqwest::blocking::Client::new()
+            .post(&self.url)
+            .bearer_auth(&self.key)
+            .json(&SmsRequest {
+                from: &self.sender,
+                to: notification.to,
+                text: &text,
+            })
+            .send()?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct WebhookRequest<'a> {
+    id: String,
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    url: &'a str,
+    locale: Locale,
+    // The short message, so chat systems accepting a `text` can show it as is
+    text: &'a str,
+}
+
+// Endpoint receiving notifications as JSON, for chat systems and other
+// integrations. With a secret, the body is signed with HMAC-SHA256 in the
+// `X-TGuard-Signature` header, so the receiver can check where it came from.
+#[derive(Deserialize)]
+pub struct Webhook {
+    pub url: String,
+    pub secret: Option<String>,
+}
+
+impl Notifier for Webhook {
+    fn notify(&self, config: &Config, notification: Notification) -> Result<(), Error> {
+        let url = notification.url(config);
+        let text = notification.short_message(config)?;
+        let body = serde_json::to_vec(&WebhookRequest {
+            id: notification.id.to_string(),
+            from: notification.from,
+            to: notification.to,
+            subject: notification.subject,
+            url: &url,
+            locale: notification.locale,
+            text: &text,
+        })?;
+
+        let mut request = reqwest::blocking::Client::new()
+            .post(&self.url)
+            .header("Content-Type", "application/json");
+        if let Some(secret) = &self.secret {
+            request = request.header("X-TGuard-Signature", signature(secret, &body));
+        }
+        request.body(body).send()?.error_for_status()?;
+
+        Ok(())
+    }
+}
+
+// Hex encoded HMAC-SHA256 of a webhook body
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod test {
+    use super::signature;
+
+    #[test]
+    fn webhook_signature() {
+        // Known answer from RFC 4231, test case 2
+        assert_eq!(
+            signature("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
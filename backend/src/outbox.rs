@@ -0,0 +1,325 @@
+use std::thread;
+use std::time::Duration;
+
+use common::{Channel, DeliveryStatus, Locale};
+use postgres::GenericClient;
+use rocket::figment::Figment;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket::tokio::runtime::{Builder, Runtime};
+
+use crate::email::{send_bounce_email, send_confirmation_email};
+use crate::events::{record, Event};
+use crate::manage::{message_owner, Authorization};
+use crate::notifier::Notification;
+use crate::{config::Config, error::Error, id::Id, Database};
+
+// Interval at which the worker checks for notifications to deliver
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// Number of notifications claimed by the worker at once, and how long they stay
+// claimed before another worker may pick them up
+const BATCH_SIZE: i64 = 20;
+const CLAIM_SECONDS: f64 = 300.0;
+
+// Kind of notification sent for a message, confirmations and bounces are
+// always emailed
+pub const NOTIFICATION: &str = "notification";
+pub const CONFIRMATION: &str = "confirmation";
+pub const BOUNCE: &str = "bounce";
+
+// Queue an email for a message, to be delivered by the worker
+pub fn enqueue<C: GenericClient>(
+    c: &mut C,
+    message: &str,
+    kind: &'static str,
+) -> Result<(), postgres::Error> {
+    c.execute("INSERT INTO outbox (message, kind) VALUES ($1, $2)", &[&message, &kind])?;
+    Ok(())
+}
+
+// The access secret for the download link in a notification is needed until it
+// is sent. It is kept in storage next to the message instead of in the
+// database, which only has its hash, so that a copy of the database is not
+// enough to download messages.
+fn access_secret_name(message: &str) -> String {
+    format!("{}.secret", message)
+}
+
+pub async fn store_access_secret(
+    config: &Config,
+    message: &str,
+    access_secret: &str,
+) -> Result<(), Error> {
+    config
+        .storage
+        .store(access_secret.as_bytes().to_vec(), &access_secret_name(message))
+        .await
+}
+
+pub async fn remove_access_secret(config: &Config, message: &str) {
+    if let Err(e) = config.storage.delete(&access_secret_name(message)).await {
+        log::error!("Failed to remove access secret of {}: {}", message, e);
+    }
+}
+
+// Seconds to wait before the next attempt, doubling from 30 seconds up to 6 hours
+fn backoff(attempts: i32) -> f64 {
+    let exponent = (attempts - 1).clamp(0, 16);
+    (30.0 * 2f64.powi(exponent)).min(6.0 * 3600.0)
+}
+
+struct Entry {
+    id: i32,
+    message: String,
+    kind: String,
+    from: String,
+    to: String,
+    subject: String,
+    attempts: i32,
+    locale: Locale,
+    link_only: bool,
+    channel: Channel,
+}
+
+// Delivers queued emails outside of requests. The worker runs on its own
+// thread, as sending is blocking, and keeps its own database connection.
+pub struct Worker {
+    config: Config,
+    database_url: String,
+    connection: Option<postgres::Client>,
+    runtime: Runtime,
+}
+
+impl Worker {
+    pub fn new(figment: &Figment) -> Result<Self, Error> {
+        let config = figment.extract::<Config>().map_err(|_| {
+            // Ignore error value, as it could contain private keys
+            log::error!("Failure to parse configuration");
+            Error::Configuration
+        })?;
+        let database_url = figment
+            .extract_inner::<String>("databases.db.url")
+            .map_err(|_| Error::Configuration)?;
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+
+        Ok(Worker {
+            config,
+            database_url,
+            connection: None,
+            runtime,
+        })
+    }
+
+    pub fn spawn(figment: &Figment) {
+        match Worker::new(figment) {
+            Ok(mut worker) => {
+                thread::spawn(move || loop {
+                    if let Err(e) = worker.process_due() {
+                        log::error!("Failed to process outbox: {}", e);
+                        worker.connection = None;
+                    }
+                    thread::sleep(POLL_INTERVAL);
+                });
+            }
+            Err(e) => log::error!("Failed to start outbox worker: {}", e),
+        }
+    }
+
+    fn connection(&mut self) -> Result<&mut postgres::Client, Error> {
+        if self.connection.is_none() {
+            self.connection = Some(postgres::Client::connect(
+                &self.database_url,
+                postgres::NoTls,
+            )?);
+        }
+        Ok(self.connection.as_mut().unwrap())
+    }
+
+    // Claim the notifications that are due, so concurrent workers skip them
+    fn claim(&mut self) -> Result<Vec<Entry>, Error> {
+        let rows = self.connection()?.query(
+            "UPDATE outbox SET attempts = outbox.attempts + 1, next_attempt = now() + $1 * INTERVAL '1 second'
+            FROM messages
+            WHERE outbox.message = messages.id AND outbox.id IN (
+                SELECT id FROM outbox WHERE status = 'pending' AND next_attempt <= now()
+                ORDER BY next_attempt LIMIT $2 FOR UPDATE SKIP LOCKED
+            )
+            RETURNING outbox.id, outbox.message, outbox.kind, messages.from_address, messages.to_address, messages.subject, outbox.attempts, messages.locale, messages.link_only, messages.channel",
+            &[&CLAIM_SECONDS, &BATCH_SIZE],
+        )?;
+
+        Ok(rows
+            .iter()
+            .map(|row| Entry {
+                id: row.get(0),
+                message: row.get(1),
+                kind: row.get(2),
+                from: row.get(3),
+                to: row.get(4),
+                subject: row.get(5),
+                attempts: row.get(6),
+                locale: Locale::from_code(row.get(7)).unwrap_or_default(),
+                link_only: row.get(8),
+                channel: Channel::from_code(row.get(9)).unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    fn access_secret(&self, entry: &Entry) -> Result<String, Error> {
+        let secret = self
+            .runtime
+            .block_on(self.config.storage.retrieve(&access_secret_name(&entry.message)))?;
+        String::from_utf8(secret).map_err(|_| Error::MissingData)
+    }
+
+    fn deliver(&self, entry: &Entry) -> Result<(), Error> {
+        let id = Id::from(entry.message.clone());
+        match entry.kind.as_str() {
+            CONFIRMATION => send_confirmation_email(
+                &self.config,
+                id,
+                Some(&self.access_secret(entry)?),
+                entry.locale,
+                &entry.to,
+                &entry.subject,
+            ),
+            // Tells the sender the notification was not delivered
+            BOUNCE => send_bounce_email(
+                &self.config,
+                Locale::for_address(&entry.from),
+                &entry.from,
+                &entry.to,
+                &entry.subject,
+            ),
+            _ => {
+                let notifier = self
+                    .config
+                    .notifier(&entry.channel)
+                    .ok_or(Error::UnsupportedChannel)?;
+                // The encrypted message is only attached to emails, and not
+                // for link-only notifications
+                let body = if entry.channel != Channel::Email || entry.link_only {
+                    None
+                } else {
+                    Some(
+                        self.runtime
+                            .block_on(self.config.storage.retrieve(&entry.message))?,
+                    )
+                };
+                notifier.notify(
+                    &self.config,
+                    Notification {
+                        id: &id,
+                        access_secret: Some(&self.access_secret(entry)?),
+                        locale: entry.locale,
+                        from: &entry.from,
+                        to: &entry.to,
+                        subject: &entry.subject,
+                        body,
+                    },
+                )
+            }
+        }
+    }
+
+    // The access secret is removed once the email with the link was sent, or
+    // will not be sent anymore
+    fn forget_access_secret(&self, entry: &Entry) {
+        if entry.kind != BOUNCE {
+            self.runtime.block_on(remove_access_secret(&self.config, &entry.message));
+        }
+    }
+
+    // Deliver all notifications that are due, returns the number delivered
+    pub fn process_due(&mut self) -> Result<usize, Error> {
+        let entries = self.claim()?;
+        let max_attempts = self.config.outbox_max_attempts;
+        let mut delivered = 0;
+
+        for entry in entries {
+            match self.deliver(&entry) {
+                Ok(()) => {
+                    let connection = self.connection()?;
+                    connection.execute(
+                        "UPDATE outbox SET status = 'sent', sent = now(), last_error = NULL WHERE id = $1",
+                        &[&entry.id],
+                    )?;
+                    if entry.kind == NOTIFICATION {
+                        record(connection, &entry.message, Event::NotificationSent)?;
+                    }
+                    self.forget_access_secret(&entry);
+                    delivered += 1;
+                }
+                Err(e) => {
+                    log::warn!(
+                        "Delivery attempt {} of {} failed: {}",
+                        entry.attempts,
+                        entry.id,
+                        e
+                    );
+                    let status = if entry.attempts >= max_attempts {
+                        "failed"
+                    } else {
+                        "pending"
+                    };
+                    let connection = self.connection()?;
+                    connection.execute(
+                        "UPDATE outbox SET status = $2, next_attempt = now() + $3 * INTERVAL '1 second', last_error = $4 WHERE id = $1",
+                        &[&entry.id, &status, &backoff(entry.attempts), &e.to_string()],
+                    )?;
+                    if entry.kind == NOTIFICATION && status == "failed" {
+                        record(connection, &entry.message, Event::NotificationBounced)?;
+                    }
+                    if status == "failed" {
+                        self.forget_access_secret(&entry);
+                    }
+                }
+            }
+        }
+
+        Ok(delivered)
+    }
+}
+
+// Only the sender may see whether and how often delivery was attempted
+#[get("/api/delivery/<id>")]
+pub async fn delivery_status(
+    config: &State<Config>,
+    conn: Database,
+    id: Id,
+    authorization: Authorization,
+) -> Result<Json<DeliveryStatus>, Error> {
+    let (from, token_hash) = message_owner(&conn, &id).await?;
+    authorization.check(config, &conn, token_hash.as_deref(), &from).await?;
+
+    let id = id.to_string();
+    conn.run(move |c| {
+        c.query(
+            "SELECT status, attempts FROM outbox WHERE message = $1 AND kind != 'bounce' ORDER BY id DESC LIMIT 1",
+            &[&id],
+        )
+    })
+    .await?
+    .get(0)
+    .map(|row| {
+        Json(DeliveryStatus {
+            status: row.get(0),
+            attempts: row.get::<_, i32>(1) as u32,
+        })
+    })
+    .ok_or(Error::NotFound)
+}
+
+#[cfg(test)]
+mod test {
+    use super::backoff;
+
+    #[test]
+    fn exponential_backoff() {
+        assert_eq!(backoff(1), 30.0);
+        assert_eq!(backoff(2), 60.0);
+        assert_eq!(backoff(5), 480.0);
+        assert_eq!(backoff(20), 6.0 * 3600.0);
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use common::identity::derive_identity;
+use common::{AttributeIdentifier, AttributeValue};
+use ibe::kiltz_vahlis_one::{extract_usk, setup, PublicKey, SecretKey};
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Config, error::Error, id::Id};
+
+// Development PKG, which hands out user secret keys for any requested identity
+// without asking for an IRMA disclosure. It mimics the endpoints of the real PKG,
+// so the frontend can be pointed at it by building with `PKG_URL={host}/pkg`.
+pub struct DevPkg {
+    public_key: PublicKey,
+    secret_key: SecretKey,
+    sessions: Mutex<HashMap<String, (AttributeValue, Instant)>>,
+    session_lifetime: Duration,
+}
+
+// Time during which keys can be fetched for a session, like the sessions of the
+// real PKG it expires afterwards
+const SESSION_LIFETIME: Duration = Duration::from_secs(300);
+
+impl DevPkg {
+    pub fn new() -> Self {
+        let (public_key, secret_key) = setup(&mut rand_07::thread_rng());
+        DevPkg {
+            public_key,
+            secret_key,
+            sessions: Mutex::new(HashMap::new()),
+            session_lifetime: SESSION_LIFETIME,
+        }
+    }
+
+    pub fn public_key(&self) -> String {
+        base64::encode(&self.public_key.to_bytes())
+    }
+
+    pub fn start_session(&self, attribute: AttributeValue) -> Id {
+        let token = Id::new();
+        let mut sessions = self.sessions.lock().unwrap();
+        // Expired sessions are dropped here, so they do not pile up
+        let lifetime = self.session_lifetime;
+        sessions.retain(|_, (_, started)| started.elapsed() < lifetime);
+        sessions.insert(token.to_string(), (attribute, Instant::now()));
+        token
+    }
+
+    // Attribute disclosed in a session, unless the session expired
+    fn session(&self, token: &Id) -> Option<AttributeValue> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(&token.to_string())
+            .filter(|(_, started)| started.elapsed() < self.session_lifetime)
+            .map(|(attribute, _)| attribute.clone())
+    }
+
+    pub fn issue_key(&self, token: &Id, timestamp: u64) -> Result<String, Error> {
+        let attribute = self.session(token).ok_or(Error::NotFound)?;
+        let identity = derive_identity(&[attribute], timestamp).ok_or(Error::InvalidAttribute)?;
+        let usk = extract_usk(
+            &self.public_key,
+            &self.secret_key,
+            &identity,
+            &mut rand_07::thread_rng(),
+        );
+
+        Ok(base64::encode(&usk.to_bytes()))
+    }
+}
+
+#[derive(Serialize)]
+pub struct Parameters {
+    public_key: String,
+}
+
+#[derive(Deserialize)]
+pub struct KeyAttribute {
+    #[serde(rename = "type")]
+    atype: String,
+    value: String,
+}
+
+#[derive(Deserialize)]
+pub struct KeyRequest {
+    attribute: KeyAttribute,
+}
+
+#[derive(Serialize)]
+pub struct KeySession {
+    qr: String,
+    token: String,
+}
+
+#[derive(Serialize)]
+pub struct KeyResponse {
+    status: &'static str,
+    key: String,
+}
+
+#[get("/pkg/v1/parameters")]
+pub fn parameters(pkg: &State<DevPkg>) -> Json<Parameters> {
+    Json(Parameters {
+        public_key: pkg.public_key(),
+    })
+}
+
+#[post("/pkg/v1/request", data = "<request>")]
+pub fn request(
+    config: &State<Config>,
+    pkg: &State<DevPkg>,
+    request: Json<KeyRequest>,
+) -> Result<Json<KeySession>, Error> {
+    let request = request.into_inner();
+    let token = pkg.start_session(AttributeValue {
+        identifier: AttributeIdentifier(request.attribute.atype),
+        value: request.attribute.value,
+    });
+
+    // The session pointer refers to the status endpoint below, which reports the
+    // session as done right away instead of waiting for the IRMA app
+    let qr = serde_json::to_string(&serde_json::json!({
+        "u": format!("{}/pkg/irma/{}", config.host, token),
+        "irmaqr": "disclosing",
+    }))?;
+
+    Ok(Json(KeySession {
+        qr,
+        token: token.to_string(),
+    }))
+}
+
+#[get("/pkg/irma/<token>/status")]
+pub fn status(pkg: &State<DevPkg>, token: Id) -> Json<&'static str> {
+    match pkg.session(&token) {
+        Some(_) => Json("DONE"),
+        None => Json("TIMEOUT"),
+    }
+}
+
+#[get("/pkg/v1/request/<token>/<timestamp>")]
+pub fn result(pkg: &State<DevPkg>, token: Id, timestamp: u64) -> Result<Json<KeyResponse>, Error> {
+    Ok(Json(KeyResponse {
+        status: "DONE_VALID",
+        key: pkg.issue_key(&token, timestamp)?,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::DevPkg;
+    use common::identity::derive_identity;
+    use common::{AttributeIdentifier, AttributeValue};
+    use core::convert::TryInto;
+    use ibe::kiltz_vahlis_one::{decrypt, encrypt, PublicKey, UserSecretKey};
+    use std::time::Duration;
+
+    #[test]
+    fn issued_key_decrypts() {
+        let pkg = DevPkg::new();
+        let attribute = AttributeValue {
+            identifier: AttributeIdentifier("pbdf.sidn-pbdf.email.email".to_owned()),
+            value: "to@example.com".to_owned(),
+        };
+        let timestamp = 1629883307;
+
+        let pk_data: [u8; 25056] = base64::decode(pkg.public_key())
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let pk = Option::<PublicKey>::from(PublicKey::from_bytes(&pk_data)).unwrap();
+        let identity = derive_identity(&[attribute.clone()], timestamp).unwrap();
+        let (c, k) = encrypt(&pk, &identity, &mut rand_07::thread_rng());
+
+        let token = pkg.start_session(attribute);
+        let usk_data: [u8; 192] = base64::decode(pkg.issue_key(&token, timestamp).unwrap())
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let usk = Option::<UserSecretKey>::from(UserSecretKey::from_bytes(&usk_data)).unwrap();
+
+        assert_eq!(decrypt(&usk, &c).to_bytes(), k.to_bytes());
+
+        // A key for a different timestamp belongs to a different identity
+        let usk_data: [u8; 192] = base64::decode(pkg.issue_key(&token, timestamp + 1).unwrap())
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let usk = Option::<UserSecretKey>::from(UserSecretKey::from_bytes(&usk_data)).unwrap();
+
+        assert_ne!(decrypt(&usk, &c).to_bytes(), k.to_bytes());
+    }
+
+    #[test]
+    fn sessions_expire() {
+        let mut pkg = DevPkg::new();
+        let attribute = AttributeValue {
+            identifier: AttributeIdentifier("pbdf.sidn-pbdf.email.email".to_owned()),
+            value: "to@example.com".to_owned(),
+        };
+        let token = pkg.start_session(attribute.clone());
+        assert!(pkg.issue_key(&token, 1629883307).is_ok());
+
+        // Expired sessions no longer hand out keys, and are removed once
+        // another session starts
+        pkg.session_lifetime = Duration::from_secs(0);
+        assert!(pkg.issue_key(&token, 1629883307).is_err());
+        pkg.start_session(attribute);
+        assert_eq!(pkg.sessions.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,375 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::{error::Error, Database};
+
+// A token bucket holding at most `capacity` tokens, refilled with `per_hour`
+// tokens every hour
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct BucketConfig {
+    pub capacity: u32,
+    pub per_hour: u32,
+}
+
+impl BucketConfig {
+    fn refill_per_second(&self) -> f64 {
+        self.per_hour as f64 / 3600.0
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum RateLimitStoreType {
+    #[serde(rename = "memory")]
+    Memory,
+    #[serde(rename = "postgres")]
+    Postgres,
+}
+
+impl Default for RateLimitStoreType {
+    fn default() -> Self {
+        RateLimitStoreType::Memory
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct RateLimitConfig {
+    #[serde(default)]
+    pub store: RateLimitStoreType,
+    pub per_ip: Option<BucketConfig>,
+    pub per_sender: Option<BucketConfig>,
+    pub per_recipient_domain: Option<BucketConfig>,
+}
+
+// Tokens to take from the bucket identified by `key`
+#[derive(Debug, Clone)]
+pub struct Take {
+    pub key: String,
+    pub bucket: BucketConfig,
+    pub cost: u32,
+}
+
+#[async_trait]
+pub trait BucketStore: Send + Sync {
+    // Take tokens from all of the given buckets, or from none of them when one
+    // does not have enough tokens. Returns whether the tokens were taken.
+    async fn take(&self, conn: &Database, takes: Vec<Take>) -> Result<bool, Error>;
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(bucket: BucketConfig, now: Instant) -> Self {
+        Bucket {
+            tokens: bucket.capacity as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, bucket: BucketConfig, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * bucket.refill_per_second())
+            .min(bucket.capacity as f64);
+        self.updated = now;
+    }
+
+    fn take(&mut self, bucket: BucketConfig, cost: u32, now: Instant) -> bool {
+        self.refill(bucket, now);
+
+        if self.tokens >= cost as f64 {
+            self.tokens -= cost as f64;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+// Interval at which buckets that have refilled are removed from memory
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+struct Buckets {
+    buckets: HashMap<String, (BucketConfig, Bucket)>,
+    swept: Instant,
+}
+
+// Buckets kept in memory, which is fine as long as a single backend is running
+pub struct MemoryStore {
+    buckets: Mutex<Buckets>,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        MemoryStore {
+            buckets: Mutex::new(Buckets {
+                buckets: HashMap::new(),
+                swept: Instant::now(),
+            }),
+        }
+    }
+}
+
+impl MemoryStore {
+    fn take_at(&self, takes: &[Take], now: Instant) -> bool {
+        let mut buckets = self.buckets.lock().unwrap();
+
+        // A full bucket is no different from a new one, so it can be removed.
+        // Keys are chosen by clients, keeping them all would use ever more
+        // memory.
+        if now.saturating_duration_since(buckets.swept) >= SWEEP_INTERVAL {
+            buckets.buckets.retain(|_, (config, bucket)| {
+                bucket.refill(*config, now);
+                bucket.tokens < config.capacity as f64
+            });
+            buckets.swept = now;
+        }
+
+        let available = takes.iter().all(|take| {
+            let (_, bucket) = buckets
+                .buckets
+                .entry(take.key.clone())
+                .or_insert_with(|| (take.bucket, Bucket::new(take.bucket, now)));
+            bucket.refill(take.bucket, now);
+            bucket.tokens >= take.cost as f64
+        });
+        if available {
+            for take in takes {
+                if let Some((_, bucket)) = buckets.buckets.get_mut(&take.key) {
+                    bucket.take(take.bucket, take.cost, now);
+                }
+            }
+        }
+        available
+    }
+}
+
+#[async_trait]
+impl BucketStore for MemoryStore {
+    async fn take(&self, _: &Database, takes: Vec<Take>) -> Result<bool, Error> {
+        Ok(self.take_at(&takes, Instant::now()))
+    }
+}
+
+// Buckets shared between backends through the rate_limits table
+pub struct PostgresStore;
+
+#[async_trait]
+impl BucketStore for PostgresStore {
+    async fn take(&self, conn: &Database, takes: Vec<Take>) -> Result<bool, Error> {
+        if takes.iter().any(|take| take.cost > take.bucket.capacity) {
+            return Ok(false);
+        }
+
+        conn.run(move |c| -> Result<bool, Error> {
+            let mut transaction = c.transaction()?;
+            for take in &takes {
+                let capacity = take.bucket.capacity as f64;
+                let cost = take.cost as f64;
+                let rate = take.bucket.refill_per_second();
+                let rows = transaction.query(
+                    "INSERT INTO rate_limits (key, tokens, updated) VALUES ($1, $2 - $3, now())
+                    ON CONFLICT (key) DO UPDATE SET
+                        tokens = LEAST($2, rate_limits.tokens + EXTRACT(EPOCH FROM now() - rate_limits.updated)::DOUBLE PRECISION * $4) - $3,
+                        updated = now()
+                    WHERE LEAST($2, rate_limits.tokens + EXTRACT(EPOCH FROM now() - rate_limits.updated)::DOUBLE PRECISION * $4) >= $3
+                    RETURNING tokens",
+                    &[&take.key, &capacity, &cost, &rate],
+                )?;
+                // Rolling back returns the tokens taken from earlier buckets
+                if rows.is_empty() {
+                    return Ok(false);
+                }
+            }
+            transaction.commit()?;
+            Ok(true)
+        })
+        .await
+    }
+}
+
+pub struct RateLimiter {
+    per_ip: Option<BucketConfig>,
+    per_sender: Option<BucketConfig>,
+    per_recipient_domain: Option<BucketConfig>,
+    store: Box<dyn BucketStore>,
+}
+
+impl From<RateLimitConfig> for RateLimiter {
+    fn from(config: RateLimitConfig) -> Self {
+        RateLimiter {
+            per_ip: config.per_ip,
+            per_sender: config.per_sender,
+            per_recipient_domain: config.per_recipient_domain,
+            store: match config.store {
+                RateLimitStoreType::Memory => Box::new(MemoryStore::default()),
+                RateLimitStoreType::Postgres => Box::new(PostgresStore),
+            },
+        }
+    }
+}
+
+fn domain(address: &str) -> String {
+    address
+        .rsplit_once('@')
+        .map(|(_, domain)| domain)
+        .unwrap_or(address)
+        .to_lowercase()
+}
+
+impl RateLimiter {
+    // Take tokens from all configured buckets, or from none of them. Tokens for
+    // the same bucket are added up, and the buckets are taken in order of their
+    // key so concurrent checks lock rows in the same order.
+    async fn check(
+        &self,
+        conn: &Database,
+        takes: Vec<(Option<BucketConfig>, String, u32)>,
+    ) -> Result<(), Error> {
+        let mut merged: BTreeMap<String, Take> = BTreeMap::new();
+        for (bucket, key, cost) in takes {
+            if let Some(bucket) = bucket {
+                merged
+                    .entry(key.clone())
+                    .or_insert(Take {
+                        key,
+                        bucket,
+                        cost: 0,
+                    })
+                    .cost += cost;
+            }
+        }
+        if merged.is_empty() {
+            return Ok(());
+        }
+
+        if self.store.take(conn, merged.into_values().collect()).await? {
+            Ok(())
+        } else {
+            Err(Error::RateLimited)
+        }
+    }
+
+    // Requests without a known client address are not limited per IP, which
+    // only happens when not running behind the proxy
+    pub async fn check_ip(&self, conn: &Database, ip: Option<IpAddr>) -> Result<(), Error> {
+        match ip {
+            Some(ip) => {
+                self.check(conn, vec![(self.per_ip, format!("ip:{}", ip), 1)])
+                    .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    // Every recipient counts against the limits of the sender address and the
+    // domain of that recipient. A message is only counted when it fits within
+    // all of these limits.
+    pub async fn check_message(
+        &self,
+        conn: &Database,
+        from: &str,
+        recipients: &[&str],
+    ) -> Result<(), Error> {
+        let mut takes = vec![(
+            self.per_sender,
+            format!("sender:{}", from.to_lowercase()),
+            recipients.len() as u32,
+        )];
+        for recipient in recipients {
+            takes.push((
+                self.per_recipient_domain,
+                format!("domain:{}", domain(recipient)),
+                1,
+            ));
+        }
+
+        self.check(conn, takes).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{domain, Bucket, BucketConfig, MemoryStore, Take, SWEEP_INTERVAL};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn token_bucket() {
+        let config = BucketConfig {
+            capacity: 3,
+            per_hour: 3600,
+        };
+        let start = Instant::now();
+        let mut bucket = Bucket::new(config, start);
+
+        assert!(bucket.take(config, 2, start));
+        assert!(bucket.take(config, 1, start));
+        assert!(!bucket.take(config, 1, start));
+
+        // One token per second is refilled
+        assert!(!bucket.take(config, 1, start + Duration::from_millis(500)));
+        assert!(bucket.take(config, 1, start + Duration::from_secs(1)));
+        assert!(!bucket.take(config, 1, start + Duration::from_secs(1)));
+
+        // Never more than the capacity
+        assert!(!bucket.take(config, 4, start + Duration::from_secs(60)));
+        assert!(bucket.take(config, 3, start + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn recipient_domain() {
+        assert_eq!(domain("someone@Example.com"), "example.com");
+        assert_eq!(domain("odd@name@example.org"), "example.org");
+    }
+
+    #[test]
+    fn all_or_nothing() {
+        let config = BucketConfig {
+            capacity: 2,
+            per_hour: 0,
+        };
+        let take = |key: &str, cost| Take {
+            key: key.to_owned(),
+            bucket: config,
+            cost,
+        };
+        let store = MemoryStore::default();
+        let now = Instant::now();
+
+        // A rejected take leaves the other buckets alone
+        assert!(store.take_at(&[take("a", 1)], now));
+        assert!(!store.take_at(&[take("b", 2), take("a", 2)], now));
+        assert!(store.take_at(&[take("b", 2), take("a", 1)], now));
+        assert!(!store.take_at(&[take("a", 1)], now));
+    }
+
+    #[test]
+    fn sweep_full_buckets() {
+        let config = BucketConfig {
+            capacity: 2,
+            per_hour: 3600,
+        };
+        let take = |key: &str| Take {
+            key: key.to_owned(),
+            bucket: config,
+            cost: 2,
+        };
+        let store = MemoryStore::default();
+        let start = Instant::now();
+
+        assert!(store.take_at(&[take("a")], start));
+        assert!(store.take_at(&[take("b")], start + SWEEP_INTERVAL - Duration::from_secs(1)));
+        assert_eq!(store.buckets.lock().unwrap().buckets.len(), 2);
+
+        // Only the bucket that has refilled completely is removed
+        assert!(store.take_at(&[], start + SWEEP_INTERVAL));
+        let buckets = store.buckets.lock().unwrap();
+        assert!(!buckets.buckets.contains_key("a"));
+        assert!(buckets.buckets.contains_key("b"));
+    }
+}
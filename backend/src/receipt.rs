@@ -0,0 +1,145 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use common::{is_receipt_message, sealed_hash, Receipt, SealedMessage};
+use irma::{ProofStatus, SessionToken};
+use postgres::Row;
+use rocket::{serde::json::Json, State};
+
+use crate::events::{record, Event};
+use crate::manage::{Authorization, EMAIL_ATTRIBUTE};
+use crate::{config::Config, error::Error, id::Id, Database};
+
+// Maximum difference in seconds between the time in a receipt and the time it
+// is submitted
+const MAX_CLOCK_SKEW: i64 = 600;
+
+// Store the receipt signed by the recipient in an IRMA session started with
+// `/api/sign`, used as the result url of that session
+#[post("/api/message/<id>/receipt?<session>&<hash>&<time>")]
+pub async fn submit_receipt(
+    config: &State<Config>,
+    conn: Database,
+    id: Id,
+    session: String,
+    hash: String,
+    time: u64,
+) -> Result<Json<Receipt>, Error> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    if (now as i64 - time as i64).abs() > MAX_CLOCK_SKEW {
+        return Err(Error::InvalidReceipt);
+    }
+
+    let result = config.irmaserver.result(&SessionToken(session)).await?;
+    if result.proof_status != Some(ProofStatus::Valid) {
+        return Err(Error::InvalidReceipt);
+    }
+    let signature = result.signature.ok_or(Error::InvalidReceipt)?;
+    let message = signature.get("message").and_then(|m| m.as_str());
+    if !message.map_or(false, |message| is_receipt_message(message, &id.to_string(), &hash, time)) {
+        return Err(Error::InvalidReceipt);
+    }
+    let recipient = result
+        .disclosed
+        .into_iter()
+        .flatten()
+        .find(|attr| attr.identifier == EMAIL_ATTRIBUTE)
+        .and_then(|attr| attr.raw_value)
+        .ok_or(Error::InvalidReceipt)?;
+
+    // The hash is only trusted when it matches the message that was stored
+    let stored = config.storage.retrieve(&id.to_string()).await?;
+    let sealed: SealedMessage =
+        serde_json::from_slice(&stored).map_err(|_| Error::InvalidReceipt)?;
+    if sealed_hash(&sealed) != hash {
+        return Err(Error::InvalidReceipt);
+    }
+
+    // The receipt is kept along with the sender, so it outlives the message
+    let row_id = id.to_string();
+    let receipt_hash = hash.clone();
+    let receipt_signature = signature.clone();
+    let to = conn
+        .run(move |c| -> Result<String, Error> {
+            let mut transaction = c.transaction()?;
+            let rows = transaction.query(
+                "SELECT from_address, to_address, management_token FROM messages WHERE id = $1",
+                &[&row_id],
+            )?;
+            let row = rows.get(0).ok_or(Error::NotFound)?;
+            let (from, to, token_hash): (String, String, Option<String>) =
+                (row.get(0), row.get(1), row.get(2));
+            if !to.eq_ignore_ascii_case(&recipient) {
+                return Err(Error::InvalidReceipt);
+            }
+
+            let inserted = transaction.execute(
+                "INSERT INTO receipts (message, from_address, to_address, management_token, hash, time, signature)
+                VALUES ($1, $2, $3, $4, $5, $6, $7) ON CONFLICT (message) DO NOTHING",
+                &[&row_id, &from, &to, &token_hash, &receipt_hash, &(time as i64), &receipt_signature],
+            )?;
+            if inserted == 1 {
+                record(&mut transaction, &row_id, Event::ReceiptSigned)?;
+            }
+            transaction.commit()?;
+            Ok(to)
+        })
+        .await?;
+
+    Ok(Json(Receipt {
+        id: id.to_string(),
+        to,
+        hash,
+        time,
+        signature,
+    }))
+}
+
+#[get("/api/message/<id>/receipt")]
+pub async fn receipt(
+    config: &State<Config>,
+    conn: Database,
+    id: Id,
+    authorization: Authorization,
+) -> Result<Json<Receipt>, Error> {
+    let row_id = id.to_string();
+    let (owner, receipt) = conn
+        .run(move |c| -> Result<(Option<(String, Option<String>)>, Option<Row>), Error> {
+            let receipt = c
+                .query(
+                    "SELECT from_address, management_token, to_address, hash, time, signature FROM receipts WHERE message = $1",
+                    &[&row_id],
+                )?
+                .into_iter()
+                .next();
+            // Before a receipt is signed, the sender is known from the message
+            let owner = match &receipt {
+                Some(row) => Some((row.get(0), row.get(1))),
+                None => c
+                    .query(
+                        "SELECT from_address, management_token FROM messages WHERE id = $1",
+                        &[&row_id],
+                    )?
+                    .get(0)
+                    .map(|row| (row.get(0), row.get(1))),
+            };
+            Ok((owner, receipt))
+        })
+        .await?;
+
+    // Unknown messages are refused like unauthorized requests, so whether a
+    // message exists is not revealed
+    let (from, token_hash) = owner.ok_or(Error::Unauthorized)?;
+    authorization.check(config, &conn, token_hash.as_deref(), &from).await?;
+    let row = receipt.ok_or(Error::NotFound)?;
+
+    Ok(Json(Receipt {
+        id: id.to_string(),
+        to: row.get(2),
+        hash: row.get(3),
+        time: row.get::<_, i64>(4) as u64,
+        signature: row.get(5),
+    }))
+}
@@ -2,7 +2,12 @@ use rocket::form::Form;
 use rocket::State;
 use serde::Deserialize;
 
-use crate::{config::Config, email::send_confirmation_email, error::Error, id::Id, Database};
+use common::{Locale, SealedMessage};
+
+use crate::bounce;
+use crate::id::{hash_secret, new_access_secret, Id};
+use crate::outbox::{self, CONFIRMATION};
+use crate::{config::Config, error::Error, Database};
 
 #[derive(Deserialize, Debug)]
 struct MailgunAttachment {
@@ -18,7 +23,26 @@ struct MailgunMessage {
     subject: String,
     #[serde(alias = "body-plain")]
     body_plain: String,
+    #[serde(default)]
     attachments: Vec<MailgunAttachment>,
+    #[serde(default, alias = "message-headers")]
+    message_headers: Vec<(String, String)>,
+}
+
+impl MailgunMessage {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.message_headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+// Full source of a stored message, as returned when asking for message/rfc822
+#[derive(Deserialize, Debug)]
+struct MailgunMime {
+    #[serde(alias = "body-mime")]
+    body_mime: String,
 }
 
 #[derive(Deserialize, FromForm, Debug)]
@@ -77,6 +101,20 @@ async fn process_message(config: &Config, conn: &Database, url: &str) -> Result<
         .json()
         .await?;
 
+    // Delivery status reports about notifications we sent are handled as
+    // bounces, which needs the full source of the report
+    if message.header("Content-Type").map_or(false, bounce::is_report) {
+        let mime: MailgunMime = client
+            .get(url)
+            .basic_auth("api", config.mailgun_key.as_ref())
+            .header("Accept", "message/rfc822")
+            .send()
+            .await?
+            .json()
+            .await?;
+        return bounce::process_report(config, conn, &mime.body_mime).await;
+    }
+
     let attachment = (|| {
         for attachment in &message.attachments {
             if attachment.content_type == "application/irmaseal"
@@ -94,19 +132,20 @@ async fn process_message(config: &Config, conn: &Database, url: &str) -> Result<
     let subject = &message.subject;
     let from = extract_original_from(&message.body_plain).unwrap_or(&config.from_fallback);
 
+    // The confirmation is sent to the address the message was received from,
+    // which could be forged
+    config
+        .rate_limiter
+        .check_message(conn, from, &[to.as_str()])
+        .await?;
+
     let row_id = id.to_string();
     let to_copy = to.to_string();
     let from_copy = from.to_string();
     let subject_copy = subject.clone();
-
-    conn.run(move |c| -> Result<(), Error> {
-        c.execute(
-            "INSERT INTO messages (id, from_address, to_address, subject) VALUES ($1, $2, $3, $4)",
-            &[&row_id, &from_copy, &to_copy, &subject_copy],
-        )?;
-        Ok(())
-    })
-    .await?;
+    let access_secret = new_access_secret();
+    let access_secret_hash = hash_secret(&access_secret);
+    let locale = Locale::for_address(to).code();
 
     let attachment = client
         .get(&attachment.url)
@@ -116,17 +155,39 @@ async fn process_message(config: &Config, conn: &Database, url: &str) -> Result<
         .bytes()
         .await?;
 
+    // The attributes and timestamp are kept with the message, like for
+    // messages submitted through the api. Attachments in other formats can
+    // not be shown on the download page.
+    let sealed = serde_json::from_slice::<SealedMessage>(&attachment).ok();
+    let attributes = match &sealed {
+        Some(sealed) => Some(serde_json::to_value(&sealed.attributes)?),
+        None => None,
+    };
+    let timestamp = sealed.map(|sealed| sealed.timestamp as i64);
+
+    conn.run(move |c| -> Result<(), Error> {
+        c.execute(
+            "INSERT INTO messages (id, from_address, to_address, subject, access_secret, locale, attributes, timestamp) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+            &[&row_id, &from_copy, &to_copy, &subject_copy, &access_secret_hash, &locale, &attributes, &timestamp],
+        )?;
+        Ok(())
+    })
+    .await?;
+
     config
         .storage
         .store(attachment.to_vec(), &id.to_string())
         .await?;
+    outbox::store_access_secret(config, &id.to_string(), &access_secret).await?;
 
-    send_confirmation_email(config, id, to, subject)?;
+    let row_id = id.to_string();
+    conn.run(move |c| outbox::enqueue(c, &row_id, CONFIRMATION)).await?;
 
     Ok(())
 }
 
-// Process a store notification as sent by mailgun
+// Process a store notification as sent by mailgun, for forwarded messages to
+// encrypt and for bounces of notifications
 #[post("/api/newemail", data = "<notification>")]
 pub async fn new_email(
     config: &State<Config>,
@@ -1,25 +1,44 @@
 use std::collections::HashMap;
+use std::net::IpAddr;
 
-use common::SignResult;
+use common::{receipt_message, signature_message, Locale, SignResult};
 use irma::{AttributeRequest, ProofStatus, SessionData, SessionToken, SignatureRequestBuilder};
 use rocket::{serde::json::Json, State};
 use serde::{Deserialize, Serialize};
 
-use crate::{config::Config, error::Error};
+use crate::{config::Config, error::Error, Database};
+
+// Message a recipient acknowledges receiving, and the time of doing so
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ReceiptFor {
+    id: String,
+    time: u64,
+}
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct SignRequest {
     hash: String,
     attributes: Vec<String>,
+    #[serde(default)]
+    receipt: Option<ReceiptFor>,
+    #[serde(default)]
+    locale: Locale,
 }
 
 #[post("/api/sign", data = "<request>")]
 pub async fn sign_message(
     config: &State<Config>,
+    conn: Database,
+    ip: Option<IpAddr>,
     request: Json<SignRequest>,
 ) -> Result<Json<SessionData>, Error> {
-    let mut sig_builder =
-        SignatureRequestBuilder::new(format!("Tguard bericht met hash {}", request.hash));
+    config.rate_limiter.check_ip(&conn, ip).await?;
+
+    let message = match &request.receipt {
+        Some(receipt) => receipt_message(&receipt.id, &request.hash, receipt.time, request.locale),
+        None => signature_message(&request.hash, request.locale),
+    };
+    let mut sig_builder = SignatureRequestBuilder::new(message);
     for attr in &request.attributes {
         if config.allowed_signing_attributes.contains(attr) {
             sig_builder =
@@ -0,0 +1,74 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
+
+use crate::error::Error;
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn mac(key: &[u8], path: &str, expires: u64) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(path.as_bytes());
+    mac.update(b"\n");
+    mac.update(expires.to_string().as_bytes());
+    mac
+}
+
+// Url for a path on this backend that is valid for the given number of
+// seconds, it can only be created with the signing key from the configuration
+pub fn sign(key: &[u8], path: &str, valid_for: u64) -> String {
+    let expires = now() + valid_for;
+    let signature = hex::encode(mac(key, path, expires).finalize().into_bytes());
+    format!("{}?expires={}&signature={}", path, expires, signature)
+}
+
+pub fn verify(key: &[u8], path: &str, expires: u64, signature: &str) -> Result<(), Error> {
+    let signature = hex::decode(signature).map_err(|_| Error::InvalidUrlSignature)?;
+    mac(key, path, expires)
+        .verify(&signature)
+        .map_err(|_| Error::InvalidUrlSignature)?;
+
+    if expires < now() {
+        return Err(Error::InvalidUrlSignature);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{mac, now, sign, verify};
+    use hmac::Mac;
+
+    fn parts(url: &str) -> (&str, u64, &str) {
+        let (path, query) = url.split_once('?').unwrap();
+        let (expires, signature) = query.split_once('&').unwrap();
+        (
+            path,
+            expires.trim_start_matches("expires=").parse().unwrap(),
+            signature.trim_start_matches("signature="),
+        )
+    }
+
+    #[test]
+    fn signed_url() {
+        let url = sign(b"key", "/api/download/abc/content", 60);
+        let (path, expires, signature) = parts(&url);
+
+        assert!(verify(b"key", path, expires, signature).is_ok());
+        assert!(verify(b"other key", path, expires, signature).is_err());
+        assert!(verify(b"key", "/api/download/def/content", expires, signature).is_err());
+        assert!(verify(b"key", path, expires + 1, signature).is_err());
+
+        // Urls that expired are rejected
+        let expired = now() - 1;
+        let signature = hex::encode(mac(b"key", path, expired).finalize().into_bytes());
+        assert!(verify(b"key", path, expired, &signature).is_err());
+    }
+}
@@ -1,19 +1,25 @@
-use crate::Error;
+use crate::{signed_url, Error};
 use cloud_storage::Object;
-use rocket::tokio::fs::File;
+use rocket::tokio::fs::{remove_file, File};
 use rocket::tokio::io::{AsyncReadExt, AsyncWriteExt};
 use std::path::Path;
 
+// Seconds during which a url to a stored file is valid
+const URL_VALIDITY: u64 = 30;
+
 #[async_trait]
 pub trait Storage: Send + Sync {
     async fn store(&self, data: Vec<u8>, file_name: &str) -> Result<(), Error>;
     async fn retrieve_url(&self, file_name: &str) -> Result<String, Error>;
+    async fn retrieve(&self, file_name: &str) -> Result<Vec<u8>, Error>;
+    async fn delete(&self, file_name: &str) -> Result<(), Error>;
     async fn serve(&self, slug: &str) -> Result<Vec<u8>, Error>;
 }
 
 pub struct LocalStorage {
     pub host: String,
     pub directory: String,
+    pub signing_key: Vec<u8>,
 }
 
 #[async_trait]
@@ -26,7 +32,22 @@ impl Storage for LocalStorage {
     }
 
     async fn retrieve_url(&self, file_name: &str) -> Result<String, Error> {
-        Ok(format!("{}/api/storage/{}", self.host, file_name))
+        let path = format!("/api/storage/{}", file_name);
+        Ok(format!(
+            "{}{}",
+            self.host,
+            signed_url::sign(&self.signing_key, &path, URL_VALIDITY)
+        ))
+    }
+
+    async fn retrieve(&self, file_name: &str) -> Result<Vec<u8>, Error> {
+        self.serve(file_name).await
+    }
+
+    async fn delete(&self, file_name: &str) -> Result<(), Error> {
+        remove_file(Path::new(&self.directory).join(file_name)).await?;
+
+        Ok(())
     }
 
     async fn serve(&self, file_name: &str) -> Result<Vec<u8>, Error> {
@@ -52,7 +73,17 @@ impl Storage for CloudStorage {
 
     async fn retrieve_url(&self, file_name: &str) -> Result<String, Error> {
         let object = Object::read(&self.bucket, file_name).await?;
-        Ok(object.download_url(30)?)
+        Ok(object.download_url(URL_VALIDITY as u32)?)
+    }
+
+    async fn retrieve(&self, file_name: &str) -> Result<Vec<u8>, Error> {
+        Ok(Object::download(&self.bucket, file_name).await?)
+    }
+
+    async fn delete(&self, file_name: &str) -> Result<(), Error> {
+        Object::delete(&self.bucket, file_name).await?;
+
+        Ok(())
     }
 
     async fn serve(&self, _: &str) -> Result<Vec<u8>, Error> {
@@ -0,0 +1,133 @@
+use std::path::Path;
+
+use common::Locale;
+use tera::{Context, Tera};
+
+use crate::error::Error;
+
+// Names of the templates for the emails sent by the backend. Tera escapes
+// values in the `.html` templates, the `.txt` templates are used for the plain
+// text parts and are not escaped. `short_message.txt` is sent by SMS and to
+// webhooks.
+const NAMES: &[&str] = &[
+    "message.txt",
+    "message.html",
+    "confirm_message.txt",
+    "confirm_message.html",
+    "bounce_message.txt",
+    "bounce_message.html",
+    "short_message.txt",
+];
+
+// Built-in templates, by locale and name
+fn builtin(locale: Locale, name: &str) -> &'static str {
+    match (locale, name) {
+        (Locale::Nl, "message.txt") => include_str!("../templates/nl/message.txt"),
+        (Locale::Nl, "message.html") => include_str!("../templates/nl/message.html"),
+        (Locale::Nl, "confirm_message.txt") => include_str!("../templates/nl/confirm_message.txt"),
+        (Locale::Nl, "confirm_message.html") => {
+            include_str!("../templates/nl/confirm_message.html")
+        }
+        (Locale::Nl, "bounce_message.txt") => include_str!("../templates/nl/bounce_message.txt"),
+        (Locale::Nl, "bounce_message.html") => {
+            include_str!("../templates/nl/bounce_message.html")
+        }
+        (Locale::Nl, "short_message.txt") => include_str!("../templates/nl/short_message.txt"),
+        (Locale::En, "message.txt") => include_str!("../templates/en/message.txt"),
+        (Locale::En, "message.html") => include_str!("../templates/en/message.html"),
+        (Locale::En, "confirm_message.txt") => include_str!("../templates/en/confirm_message.txt"),
+        (Locale::En, "confirm_message.html") => {
+            include_str!("../templates/en/confirm_message.html")
+        }
+        (Locale::En, "bounce_message.txt") => include_str!("../templates/en/bounce_message.txt"),
+        (Locale::En, "bounce_message.html") => {
+            include_str!("../templates/en/bounce_message.html")
+        }
+        (Locale::En, "short_message.txt") => include_str!("../templates/en/short_message.txt"),
+        _ => unreachable!("unknown template {}", name),
+    }
+}
+
+// Templates are registered as `{locale}/{name}`, matching their path in the
+// template directory
+fn template_name(locale: Locale, name: &str) -> String {
+    format!("{}/{}", locale.code(), name)
+}
+
+pub struct Templates {
+    tera: Tera,
+}
+
+impl Templates {
+    // Load the built-in templates, replacing those present in `directory`, so
+    // deployments can change the emails without recompiling
+    pub fn new(directory: Option<&str>) -> Result<Self, Error> {
+        let mut tera = Tera::default();
+        for locale in Locale::ALL {
+            for name in NAMES {
+                tera.add_raw_template(&template_name(locale, name), builtin(locale, name))?;
+            }
+        }
+
+        if let Some(directory) = directory {
+            for locale in Locale::ALL {
+                for name in NAMES {
+                    let template = template_name(locale, name);
+                    let path = Path::new(directory).join(&template);
+                    if path.exists() {
+                        log::info!("Using email template {}", path.display());
+                        tera.add_template_file(&path, Some(&template))?;
+                    }
+                }
+            }
+        }
+
+        Ok(Templates { tera })
+    }
+
+    pub fn render(&self, locale: Locale, name: &str, context: &Context) -> Result<String, Error> {
+        Ok(self.tera.render(&template_name(locale, name), context)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Templates;
+    use common::Locale;
+    use tera::Context;
+
+    #[test]
+    fn escapes_html() {
+        let templates = Templates::new(None).unwrap();
+        let mut context = Context::new();
+        context.insert("from", "<b>from@example.com</b>");
+        context.insert("subject", "<script>alert(1)</script>");
+        context.insert("url", "https://example.com/download/abc#def");
+
+        let html = templates
+            .render(Locale::En, "message.html", &context)
+            .unwrap();
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;b&gt;from@example.com&lt;&#x2F;b&gt;"));
+
+        let text = templates
+            .render(Locale::En, "message.txt", &context)
+            .unwrap();
+        assert!(text.contains("<b>from@example.com</b>"));
+        assert!(text.contains("https://example.com/download/abc#def"));
+    }
+
+    #[test]
+    fn localized() {
+        let templates = Templates::new(None).unwrap();
+        let mut context = Context::new();
+        context.insert("from", "from@example.com");
+        context.insert("subject", "Subject");
+        context.insert("url", "https://example.com/nl/download/abc#def");
+
+        let text = templates
+            .render(Locale::Nl, "message.txt", &context)
+            .unwrap();
+        assert!(text.starts_with("U heeft een bericht ontvangen van from@example.com"));
+    }
+}
@@ -0,0 +1,76 @@
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread::JoinHandle;
+
+// Local stand-in for an HTTP service like an SMS gateway, webhook or mail
+// provider, answering a single request. Returns its url, ending in `path`, and
+// the request it received, headers lowercased.
+pub fn stand_in(path: &str) -> (String, JoinHandle<String>) {
+    let (url, server) = serve(path, vec![String::new()]);
+    (url, std::thread::spawn(move || server.join().unwrap().remove(0)))
+}
+
+// Local stand-in for an HTTP API, answering a request with each of the JSON
+// bodies in turn. Returns its url, ending in `path`, and the requests it
+// received.
+pub fn serve(path: &str, responses: Vec<String>) -> (String, JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}{}", listener.local_addr().unwrap(), path);
+    let server = std::thread::spawn(move || {
+        let mut requests = Vec::new();
+        for response in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0; 4096];
+            loop {
+                let read = stream.read(&mut buffer).unwrap();
+                request.extend_from_slice(&buffer[..read]);
+                if read == 0 || complete(&request) {
+                    break;
+                }
+            }
+            let content_type = if response.is_empty() {
+                ""
+            } else {
+                "Content-Type: application/json\r\n"
+            };
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                content_type,
+                response.len(),
+                response
+            )
+            .unwrap();
+            let request = String::from_utf8_lossy(&request).into_owned();
+            requests.push(match request.split_once("\r\n\r\n") {
+                Some((head, body)) => format!("{}\r\n\r\n{}", head.to_lowercase(), body),
+                None => request,
+            });
+        }
+        requests
+    });
+    (url, server)
+}
+
+// Whether the headers and the complete body of a request were received
+fn complete(request: &[u8]) -> bool {
+    let request = String::from_utf8_lossy(request);
+    let (head, body) = match request.split_once("\r\n\r\n") {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let header = |header: &str| {
+        head.lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case(header))
+            .map(|(_, value)| value.trim().to_owned())
+    };
+    if header("transfer-encoding").map_or(false, |value| value.eq_ignore_ascii_case("chunked")) {
+        return body.ends_with("0\r\n\r\n");
+    }
+    let length = header("content-length")
+        .and_then(|value| value.parse::<usize>().ok())
+        .unwrap_or(0);
+    body.len() >= length
+}
@@ -0,0 +1,19 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Sending failed: {0}")]
+    Send(#[from] tguard_client::SendError),
+    #[error("{0}")]
+    Client(#[from] tguard_client::Error),
+    #[error("File error: {0}")]
+    File(#[from] std::io::Error),
+    #[error("Invalid encoding: {0}")]
+    Encoding(#[from] serde_json::Error),
+    #[error("Decryption error: {0}")]
+    Seal(#[from] common::ibs::SealError),
+    #[error("Key session did not complete")]
+    Session,
+    #[error("Sealed message has no message for {0}")]
+    NoRecipientMessage(String),
+}
@@ -0,0 +1,505 @@
+mod error;
+
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use clap::Parser;
+use common::ibs::{parse_user_secret_key, unseal};
+use common::{DownloadLimit, Locale, MessageData, SendResult};
+use tguard_client::{Channel, Client, Message, Recipient, DEFAULT_PKG};
+
+use crate::error::Error;
+
+// Recipients are given as `email[,attribute=value...]`, the message is always
+// sealed for the email address, followed by any additional attributes. With
+// `sms:number` the recipient is notified by SMS and the message is sealed for
+// the phone number instead. With `webhook:name:address` the recipient is
+// notified through a webhook of the backend, and the message is only sealed for
+// the attributes given.
+#[derive(Debug)]
+struct RecipientArg(Recipient);
+
+impl FromStr for RecipientArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',');
+        let to = parts.next().unwrap_or_default().trim();
+        if to.is_empty() {
+            return Err("missing recipient address".to_owned());
+        }
+
+        let mut recipient = if let Some(number) = to.strip_prefix("sms:") {
+            Recipient::sms(number)
+        } else if let Some(webhook) = to.strip_prefix("webhook:") {
+            let (name, address) = webhook
+                .split_once(':')
+                .ok_or_else(|| format!("invalid webhook recipient '{}'", to))?;
+            Recipient {
+                to: address.to_owned(),
+                channel: Channel::Webhook(name.to_owned()),
+                attributes: vec![],
+            }
+        } else {
+            Recipient::email(to)
+        };
+        for part in parts {
+            let (identifier, value) = part
+                .split_once('=')
+                .map(|(identifier, value)| (identifier.trim(), value.trim()))
+                .filter(|(identifier, value)| !identifier.is_empty() && !value.is_empty())
+                .ok_or_else(|| format!("invalid attribute '{}'", part))?;
+            recipient = recipient.attribute(identifier, value);
+        }
+        if recipient.attributes.is_empty() {
+            return Err(format!("no attributes to seal for '{}'", to));
+        }
+
+        Ok(RecipientArg(recipient))
+    }
+}
+
+#[derive(Debug)]
+struct DownloadLimitArg(DownloadLimit);
+
+impl FromStr for DownloadLimitArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "decrypted" => Ok(DownloadLimitArg(DownloadLimit::UntilDecrypted)),
+            _ => match s.parse::<u32>() {
+                Ok(count) if count > 0 => Ok(DownloadLimitArg(DownloadLimit::Count(count))),
+                _ => Err(format!("invalid download limit '{}'", s)),
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+struct LocaleArg(Locale);
+
+impl FromStr for LocaleArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Locale::from_code(s)
+            .map(LocaleArg)
+            .ok_or_else(|| format!("unsupported locale '{}'", s))
+    }
+}
+
+#[derive(Parser)]
+struct MessageOpts {
+    /// Email address of the sender
+    #[clap(long)]
+    from: String,
+    /// Recipient, as `email[,attribute=value...]`, `sms:number[,...]` or
+    /// `webhook:name:address,attribute=value[,...]`
+    #[clap(long, required = true)]
+    to: Vec<RecipientArg>,
+    #[clap(long)]
+    subject: String,
+    /// Message text
+    #[clap(long, default_value = "")]
+    message: String,
+    /// File to attach to the message
+    #[clap(long)]
+    attach: Vec<PathBuf>,
+    /// Delete the message after this many downloads, or `decrypted` to delete
+    /// it once the recipient decrypted it
+    #[clap(long)]
+    download_limit: Option<DownloadLimitArg>,
+    /// Language of the notification email, `nl` or `en`, by default derived
+    /// from each recipient's address
+    #[clap(long)]
+    locale: Option<LocaleArg>,
+    /// Only send the download link to recipients, without attaching the
+    /// encrypted message to the email
+    #[clap(long)]
+    link_only: bool,
+    /// Do not notify recipients, print the download links to hand them over
+    /// yourself instead
+    #[clap(long)]
+    share_link: bool,
+    /// Also send a copy to the sender, which they can decrypt with their own
+    /// email address
+    #[clap(long)]
+    sender_copy: bool,
+}
+
+impl MessageOpts {
+    fn to_message(&self) -> Result<Message, Error> {
+        let mut builder = Message::builder(&self.from, &self.subject).text(&self.message);
+        for RecipientArg(recipient) in &self.to {
+            builder = builder.recipient(recipient.clone());
+        }
+        for path in &self.attach {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "attachment.bin".to_owned());
+            let content_type = mime_guess::from_path(path)
+                .first_raw()
+                .unwrap_or("application/octet-stream");
+            builder = builder.attachment(&name, content_type, std::fs::read(path)?);
+        }
+        if let Some(DownloadLimitArg(limit)) = &self.download_limit {
+            builder = builder.download_limit(*limit);
+        }
+        if let Some(LocaleArg(locale)) = &self.locale {
+            builder = builder.locale(*locale);
+        }
+        if self.link_only {
+            builder = builder.link_only();
+        }
+        if self.share_link {
+            builder = builder.share_link();
+        }
+        if self.sender_copy {
+            builder = builder.sender_copy();
+        }
+
+        Ok(builder.build())
+    }
+}
+
+#[derive(Parser)]
+enum Command {
+    /// Seal a message, and write the sealed message as JSON
+    Seal {
+        #[clap(flatten)]
+        message: MessageOpts,
+        /// Output file, defaults to stdout
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Submit a sealed message to the TGuard backend
+    Submit {
+        /// Sealed message as produced by `seal`
+        input: PathBuf,
+    },
+    /// Seal a message and submit it to the TGuard backend
+    Send {
+        #[clap(flatten)]
+        message: MessageOpts,
+    },
+    /// Withdraw a sent message, it can no longer be downloaded afterwards
+    Withdraw {
+        id: String,
+        /// Management token as printed by `send` or `submit`
+        #[clap(long, env = "TGUARD_MANAGEMENT_TOKEN")]
+        management_token: String,
+    },
+    /// Show the events recorded for a sent message, one per line
+    Status {
+        id: String,
+        /// Management token as printed by `send` or `submit`
+        #[clap(long, env = "TGUARD_MANAGEMENT_TOKEN")]
+        management_token: String,
+    },
+    /// Fetch the receipt signed by the recipient of a sent message, as JSON
+    Receipt {
+        id: String,
+        /// Management token as printed by `send` or `submit`
+        #[clap(long, env = "TGUARD_MANAGEMENT_TOKEN")]
+        management_token: String,
+        /// Sealed message as produced by `seal`, to verify the receipt against
+        #[clap(long)]
+        verify: Option<PathBuf>,
+        /// Url of the signature verifier, defaults to `{server}/api/verify`
+        #[clap(long)]
+        verifier: Option<String>,
+        /// Output file for the receipt, defaults to stdout
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Download and decrypt a message, verifying its signature if it has one
+    Download {
+        /// Download link as received by email, or the message id
+        link: String,
+        /// Access secret of the message, when only the id is given
+        #[clap(long, env = "TGUARD_ACCESS_SECRET")]
+        access_secret: Option<String>,
+        /// File containing the base64 encoded user secret key
+        #[clap(long, conflicts_with = "pkg-session")]
+        usk_file: Option<PathBuf>,
+        /// PKG session url of the form `{pkg}/v1/request/{token}`
+        #[clap(long)]
+        pkg_session: Option<String>,
+        /// Url of the signature verifier, defaults to `{server}/api/verify`
+        #[clap(long)]
+        verifier: Option<String>,
+        /// Output file for the decrypted message, defaults to stdout
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Parser)]
+#[clap(name = "tguard-cli", about = "Send and receive messages using TGuard")]
+struct Opts {
+    /// Url of the TGuard backend
+    #[clap(long, env = "TGUARD_SERVER")]
+    server: String,
+    /// Url of the PKG
+    #[clap(long, env = "TGUARD_PKG", default_value = DEFAULT_PKG)]
+    pkg: String,
+    /// API key issued for this system
+    #[clap(long, env = "TGUARD_API_KEY")]
+    api_key: Option<String>,
+    #[clap(subcommand)]
+    command: Command,
+}
+
+fn write_output(output: &Option<PathBuf>, data: &[u8]) -> Result<(), Error> {
+    match output {
+        Some(path) => std::fs::write(path, data)?,
+        None => {
+            use std::io::Write;
+            std::io::stdout().write_all(data)?;
+        }
+    }
+    Ok(())
+}
+
+fn report_sent(result: SendResult) {
+    for sent in result.accepted {
+        print!(
+            "{}\t{}\t{}\t{}",
+            sent.id, sent.to, sent.management_token, sent.access_secret
+        );
+        // Shared links come with the download link to hand over
+        match sent.url {
+            Some(url) => println!("\t{}", url),
+            None => println!(),
+        }
+    }
+}
+
+// Split a download link of the form `{host}/download/{id}#{secret}` into the
+// message id and access secret
+fn parse_link(link: &str) -> (&str, Option<&str>) {
+    let (path, secret) = match link.split_once('#') {
+        Some((path, secret)) => (path, Some(secret)),
+        None => (link, None),
+    };
+    let id = path.rsplit('/').next().unwrap_or(path);
+    (id, secret.filter(|secret| !secret.is_empty()))
+}
+
+async fn download(
+    client: &Client,
+    id: &str,
+    access_secret: Option<&str>,
+    usk_file: &Option<PathBuf>,
+    pkg_session: &Option<String>,
+    verifier: &Option<String>,
+) -> Result<Vec<u8>, Error> {
+    let metadata = client.download(id, access_secret).await?;
+
+    let usk = match (usk_file, pkg_session) {
+        (Some(path), _) => parse_user_secret_key(std::fs::read_to_string(path)?.trim())?,
+        (None, Some(session)) => client
+            .session_url_key(session, metadata.timestamp)
+            .await?
+            .ok_or(Error::Session)?,
+        (None, None) => {
+            // Start a new session at the PKG and wait until the attribute is disclosed
+            let attribute = metadata.attributes.get(0).ok_or(Error::Session)?;
+            let session = client.start_key_session(attribute).await?;
+            eprintln!("Disclose {} using the IRMA app:", attribute.identifier.0);
+            eprintln!("{}", session.qr);
+
+            let mut usk = None;
+            for _ in 0..150 {
+                usk = client.session_key(&session.token, metadata.timestamp).await?;
+                if usk.is_some() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_secs(2)).await;
+            }
+            usk.ok_or(Error::Session)?
+        }
+    };
+
+    // The content is only fetched once a key is available, so the sender sees
+    // the message as downloaded when it is actually read
+    let sealed = client.fetch_sealed(&metadata.content).await?;
+    let plain = unseal(&sealed, &usk)?;
+
+    if metadata.signature.is_some() {
+        let verifier = verifier
+            .clone()
+            .unwrap_or_else(|| client.default_verifier());
+        client
+            .verify_signature(&verifier, &metadata, &plain)
+            .await?;
+        eprintln!("Message signed by {}", metadata.from);
+    }
+
+    if metadata.download_limit.is_some() {
+        client.confirm_decrypted(id, access_secret).await?;
+    }
+
+    Ok(plain)
+}
+
+async fn run(opts: &Opts) -> Result<(), Error> {
+    let mut client = Client::new(&opts.server).pkg(&opts.pkg);
+    if let Some(key) = &opts.api_key {
+        client = client.api_key(key);
+    }
+
+    match &opts.command {
+        Command::Seal { message, output } => {
+            let pk = client.public_key().await?;
+            let data = client.seal(&pk, &message.to_message()?)?;
+            write_output(output, &serde_json::to_vec(&data)?)
+        }
+        Command::Submit { input } => {
+            let data: MessageData = serde_json::from_slice(&std::fs::read(input)?)?;
+            report_sent(client.submit(&data).await?);
+            Ok(())
+        }
+        Command::Send { message } => {
+            report_sent(client.send(&message.to_message()?).await?);
+            Ok(())
+        }
+        Command::Withdraw {
+            id,
+            management_token,
+        } => Ok(client.withdraw(id, management_token).await?),
+        Command::Status {
+            id,
+            management_token,
+        } => {
+            let status = client.message_status(id, management_token).await?;
+            for event in status.events {
+                println!("{}\t{}", event.time, event.event);
+            }
+            Ok(())
+        }
+        Command::Receipt {
+            id,
+            management_token,
+            verify,
+            verifier,
+            output,
+        } => {
+            let receipt = client.receipt(id, management_token).await?;
+            if let Some(path) = verify {
+                let verifier = verifier
+                    .clone()
+                    .unwrap_or_else(|| client.default_verifier());
+                let data: MessageData = serde_json::from_slice(&std::fs::read(path)?)?;
+                let message = data
+                    .recipient_messages
+                    .iter()
+                    .find(|message| !message.sender_copy && message.to == receipt.to)
+                    .ok_or_else(|| Error::NoRecipientMessage(receipt.to.clone()))?;
+                client
+                    .verify_receipt(&verifier, &receipt, &message.sealed)
+                    .await?;
+                eprintln!("Receipt signed by {}", receipt.to);
+            }
+            write_output(output, &serde_json::to_vec(&receipt)?)
+        }
+        Command::Download {
+            link,
+            access_secret,
+            usk_file,
+            pkg_session,
+            verifier,
+            output,
+        } => {
+            let (id, secret) = parse_link(link);
+            let secret = secret.or(access_secret.as_deref());
+            let plain = download(&client, id, secret, usk_file, pkg_session, verifier).await?;
+            write_output(output, &plain)
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let opts = Opts::parse();
+
+    if let Err(e) = run(&opts).await {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_link, DownloadLimitArg, LocaleArg, RecipientArg};
+    use common::{DownloadLimit, Locale};
+    use tguard_client::{Channel, Recipient};
+
+    fn recipient(arg: &str) -> Result<Recipient, String> {
+        arg.parse::<RecipientArg>().map(|RecipientArg(recipient)| recipient)
+    }
+
+    #[test]
+    fn recipient_arg() {
+        assert_eq!(recipient("to@example.com").unwrap(), Recipient::email("to@example.com"));
+        assert_eq!(
+            recipient(" to@example.com , pbdf.gemeente.personalData.bsn = 999999990").unwrap(),
+            Recipient::email("to@example.com")
+                .attribute("pbdf.gemeente.personalData.bsn", "999999990")
+        );
+        assert_eq!(recipient("sms:+31612345678").unwrap(), Recipient::sms("+31612345678"));
+
+        let webhook =
+            recipient("webhook:chat:@jan,pbdf.sidn-pbdf.email.email=jan@example.com").unwrap();
+        assert_eq!(webhook.to, "@jan");
+        assert_eq!(webhook.channel, Channel::Webhook("chat".to_owned()));
+        assert_eq!(webhook.attributes.len(), 1);
+    }
+
+    #[test]
+    fn recipient_arg_invalid() {
+        for arg in [
+            "",
+            " ,pbdf.sidn-pbdf.email.email=to@example.com",
+            "to@example.com,",
+            "to@example.com,pbdf.gemeente.personalData.bsn",
+            "to@example.com,=999999990",
+            "to@example.com,pbdf.gemeente.personalData.bsn=",
+            "webhook:chat",
+            "webhook:chat:@jan",
+        ] {
+            assert!(recipient(arg).is_err(), "accepted '{}'", arg);
+        }
+    }
+
+    #[test]
+    fn download_limit_arg() {
+        let limit = |arg: &str| arg.parse::<DownloadLimitArg>().map(|DownloadLimitArg(l)| l);
+        assert_eq!(limit("3"), Ok(DownloadLimit::Count(3)));
+        assert_eq!(limit("decrypted"), Ok(DownloadLimit::UntilDecrypted));
+        for arg in ["0", "-1", "", "three", "1.5", "Decrypted"] {
+            assert!(limit(arg).is_err(), "accepted '{}'", arg);
+        }
+    }
+
+    #[test]
+    fn locale_arg() {
+        assert_eq!("en".parse::<LocaleArg>().map(|LocaleArg(l)| l), Ok(Locale::En));
+        assert!("de".parse::<LocaleArg>().is_err());
+    }
+
+    #[test]
+    fn download_link() {
+        assert_eq!(
+            parse_link("https://example.com/en/download/abc#secret"),
+            ("abc", Some("secret"))
+        );
+        assert_eq!(parse_link("https://example.com/download/abc"), ("abc", None));
+        assert_eq!(parse_link("https://example.com/download/abc#"), ("abc", None));
+        assert_eq!(parse_link("abc#secret"), ("abc", Some("secret")));
+        assert_eq!(parse_link("abc"), ("abc", None));
+    }
+}
@@ -0,0 +1,40 @@
+use common::ibs::SealError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SendError {
+    #[error("public key not found")]
+    MissingKey,
+    #[error("failed to encrypt the message: {0}")]
+    FailedSeal(#[from] SealError),
+    #[error("invalid signature")]
+    SignError,
+    #[error("failed to serialize the message")]
+    SerializeError,
+    #[error("message could not be sent: {0}")]
+    NotSent(#[from] reqwest::Error),
+    #[error("message rejected: {0}")]
+    Rejected(String),
+    #[error("the message is too large")]
+    TooLarge,
+    #[error("missing or invalid API key")]
+    Unauthorized,
+    #[error("too many requests: {0}")]
+    TooManyRequests(String),
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Request failed: {0}")]
+    Reqwest(#[from] reqwest::Error),
+    #[error("Invalid encoding: {0}")]
+    Encoding(#[from] serde_json::Error),
+    #[error("Decryption error: {0}")]
+    Seal(#[from] SealError),
+    #[error("Not found")]
+    NotFound,
+    #[error("Key session did not complete")]
+    Session,
+    #[error("Invalid signature")]
+    InvalidSignature,
+}
@@ -0,0 +1,605 @@
+mod error;
+
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use common::ibs::{parse_public_key, parse_user_secret_key, seal};
+use ibe::kiltz_vahlis_one::{PublicKey, UserSecretKey};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+pub use common::{
+    AttributeIdentifier, AttributeValue, Channel, DeliveryStatus, DownloadLimit, DownloadResult,
+    Locale, MessageData, MessageEvent, MessageStatus, Receipt, RecipientMessage, SealedMessage,
+    SendResult,
+};
+pub use common::mime::Attachment;
+pub use error::{Error, SendError};
+
+pub const EMAIL_ATTRIBUTE_IDENTIFIER: &str = "pbdf.sidn-pbdf.email.email";
+pub const PHONE_NUMBER_ATTRIBUTE_IDENTIFIER: &str = "pbdf.sidn-pbdf.mobilenumber.mobilenumber";
+pub const DEFAULT_PKG: &str = "https://irmacrypt.nl/pkg";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recipient {
+    pub to: String,
+    pub channel: Channel,
+    pub attributes: Vec<AttributeValue>,
+}
+
+impl Recipient {
+    /// Recipient that needs to disclose its email address to read the message.
+    pub fn email(to: &str) -> Self {
+        Recipient {
+            to: to.to_owned(),
+            channel: Channel::Email,
+            attributes: vec![AttributeValue {
+                identifier: AttributeIdentifier(EMAIL_ATTRIBUTE_IDENTIFIER.to_owned()),
+                value: to.to_owned(),
+            }],
+        }
+    }
+
+    /// Recipient that is notified by SMS, and needs to disclose its phone
+    /// number to read the message. The number is in international format.
+    pub fn sms(number: &str) -> Self {
+        Recipient {
+            to: number.to_owned(),
+            channel: Channel::Sms,
+            attributes: vec![AttributeValue {
+                identifier: AttributeIdentifier(PHONE_NUMBER_ATTRIBUTE_IDENTIFIER.to_owned()),
+                value: number.to_owned(),
+            }],
+        }
+    }
+
+    /// Notify the recipient through another channel, `to` has to be an
+    /// address for that channel.
+    pub fn channel(mut self, channel: Channel) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    /// Additionally require the recipient to disclose the given attribute value.
+    pub fn attribute(mut self, identifier: &str, value: &str) -> Self {
+        self.attributes.push(AttributeValue {
+            identifier: AttributeIdentifier(identifier.to_owned()),
+            value: value.to_owned(),
+        });
+        self
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    pub from: String,
+    pub subject: String,
+    pub text: String,
+    pub recipients: Vec<Recipient>,
+    pub attachments: Vec<Attachment>,
+    pub signature: Option<String>,
+    pub download_limit: Option<DownloadLimit>,
+    pub locale: Option<Locale>,
+    pub link_only: bool,
+    pub share_link: bool,
+    pub sender_copy: bool,
+}
+
+impl Message {
+    pub fn builder(from: &str, subject: &str) -> MessageBuilder {
+        MessageBuilder {
+            message: Message {
+                from: from.to_owned(),
+                subject: subject.to_owned(),
+                text: String::new(),
+                recipients: vec![],
+                attachments: vec![],
+                signature: None,
+                download_limit: None,
+                locale: None,
+                link_only: false,
+                share_link: false,
+                sender_copy: false,
+            },
+        }
+    }
+
+    /// The plaintext as it is sealed for every recipient.
+    pub fn packed(&self) -> Result<String, SendError> {
+        common::mime::convert_to_mime(&mut rand::thread_rng(), &self.text, &self.attachments)
+            .ok_or(SendError::SerializeError)
+    }
+}
+
+pub struct MessageBuilder {
+    message: Message,
+}
+
+impl MessageBuilder {
+    pub fn text(mut self, text: &str) -> Self {
+        self.message.text = text.to_owned();
+        self
+    }
+
+    pub fn recipient(mut self, recipient: Recipient) -> Self {
+        self.message.recipients.push(recipient);
+        self
+    }
+
+    pub fn attachment(mut self, name: &str, content_type: &str, content: Vec<u8>) -> Self {
+        self.message.attachments.push(Attachment {
+            name: name.to_owned(),
+            content_type: content_type.to_owned(),
+            content,
+        });
+        self
+    }
+
+    /// Attach an IRMA signature, as obtained through `/api/sign`, over the hash of
+    /// the packed message.
+    pub fn signature(mut self, signature: &str) -> Self {
+        self.message.signature = Some(signature.to_owned());
+        self
+    }
+
+    /// Delete the message once its content was fetched a number of times, or
+    /// once the recipient decrypted it.
+    pub fn download_limit(mut self, limit: DownloadLimit) -> Self {
+        self.message.download_limit = Some(limit);
+        self
+    }
+
+    /// Language of the notification email and download page, by default it is
+    /// derived from each recipient's address.
+    pub fn locale(mut self, locale: Locale) -> Self {
+        self.message.locale = Some(locale);
+        self
+    }
+
+    /// Never attach the encrypted message to the notification emails, they
+    /// only contain the download link. Large messages are never attached.
+    pub fn link_only(mut self) -> Self {
+        self.message.link_only = true;
+        self
+    }
+
+    /// Do not notify the recipients, the download links are returned in the
+    /// `url` of each sent message for the sender to hand over.
+    pub fn share_link(mut self) -> Self {
+        self.message.share_link = true;
+        self
+    }
+
+    /// Also seal the message for the sender's email address and send them the
+    /// copy, so they can read what they sent later.
+    pub fn sender_copy(mut self) -> Self {
+        self.message.sender_copy = true;
+        self
+    }
+
+    pub fn build(self) -> Message {
+        self.message
+    }
+}
+
+#[derive(Deserialize)]
+struct Parameters {
+    public_key: String,
+}
+
+#[derive(Serialize)]
+struct KeyAttribute<'a> {
+    #[serde(rename = "type")]
+    atype: &'a str,
+    value: &'a str,
+}
+
+#[derive(Serialize)]
+struct KeyRequest<'a> {
+    attribute: KeyAttribute<'a>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct KeySession {
+    /// Session pointer to show to the IRMA app
+    pub qr: String,
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+struct KeyResponse {
+    status: String,
+    key: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SigData {
+    message: String,
+}
+
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    server: String,
+    pkg: String,
+    api_key: Option<String>,
+}
+
+impl Client {
+    pub fn new(server: &str) -> Self {
+        Client {
+            http: reqwest::Client::new(),
+            server: server.trim_end_matches('/').to_owned(),
+            pkg: DEFAULT_PKG.to_owned(),
+            api_key: None,
+        }
+    }
+
+    /// Authenticate submissions with an API key issued for this system.
+    pub fn api_key(mut self, key: &str) -> Self {
+        self.api_key = Some(key.to_owned());
+        self
+    }
+
+    pub fn pkg(mut self, pkg: &str) -> Self {
+        self.pkg = pkg.trim_end_matches('/').to_owned();
+        self
+    }
+
+    pub async fn public_key(&self) -> Result<PublicKey, SendError> {
+        let parameters: Parameters = self
+            .http
+            .get(format!("{}/v1/parameters", self.pkg))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|_| SendError::MissingKey)?
+            .json()
+            .await
+            .map_err(|_| SendError::MissingKey)?;
+
+        parse_public_key(&parameters.public_key).map_err(|_| SendError::MissingKey)
+    }
+
+    /// Seal the message for all its recipients, using the current time.
+    pub fn seal(&self, pk: &PublicKey, message: &Message) -> Result<MessageData, SendError> {
+        let packed = message.packed()?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let mut rng = rand::thread_rng();
+        let mut recipient_messages = message
+            .recipients
+            .iter()
+            .map(|to| {
+                seal(
+                    pk,
+                    &to.to,
+                    &to.attributes,
+                    packed.as_bytes(),
+                    timestamp,
+                    &mut rng,
+                )
+                .map(|sealed| RecipientMessage {
+                    channel: to.channel.clone(),
+                    ..sealed
+                })
+            })
+            .collect::<Result<Vec<RecipientMessage>, _>>()?;
+
+        // The copy for the sender is sealed for their own email address
+        if message.sender_copy {
+            let copy = Recipient::email(&message.from);
+            let sealed = seal(
+                pk,
+                &copy.to,
+                &copy.attributes,
+                packed.as_bytes(),
+                timestamp,
+                &mut rng,
+            )?;
+            recipient_messages.push(RecipientMessage {
+                sender_copy: true,
+                ..sealed
+            });
+        }
+
+        Ok(MessageData {
+            from: message.from.clone(),
+            subject: message.subject.clone(),
+            recipient_messages,
+            signature: message.signature.clone(),
+            download_limit: message.download_limit,
+            locale: message.locale,
+            link_only: message.link_only,
+            share_link: message.share_link,
+        })
+    }
+
+    pub async fn submit(&self, data: &MessageData) -> Result<SendResult, SendError> {
+        let mut request = self.http.post(format!("{}/api", self.server)).json(data);
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+        let response = request.send().await?;
+
+        match response.status() {
+            s if s.is_success() => Ok(response.json().await?),
+            StatusCode::PAYLOAD_TOO_LARGE => Err(SendError::TooLarge),
+            StatusCode::UNAUTHORIZED => Err(SendError::Unauthorized),
+            StatusCode::TOO_MANY_REQUESTS => {
+                Err(SendError::TooManyRequests(response.text().await?))
+            }
+            StatusCode::BAD_REQUEST | StatusCode::FORBIDDEN => {
+                // The backend rejects oversized messages with a 400 as well
+                match response.text().await? {
+                    body if body == common::TOO_LARGE => Err(SendError::TooLarge),
+                    body => Err(SendError::Rejected(body)),
+                }
+            }
+            _ => Err(response.error_for_status().unwrap_err().into()),
+        }
+    }
+
+    /// Seal the message against the public key of the PKG and submit it.
+    pub async fn send(&self, message: &Message) -> Result<SendResult, SendError> {
+        let pk = self.public_key().await?;
+        let data = self.seal(&pk, message)?;
+        self.submit(&data).await
+    }
+
+    pub async fn download(
+        &self,
+        id: &str,
+        access_secret: Option<&str>,
+    ) -> Result<DownloadResult, Error> {
+        let mut request = self
+            .http
+            .get(format!("{}/api/download/{}", self.server, id));
+        if let Some(secret) = access_secret {
+            request = request.header("X-Access-Secret", secret);
+        }
+        let response = request.send().await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(Error::NotFound);
+        }
+
+        Ok(response.error_for_status()?.json().await?)
+    }
+
+    /// Withdraw a message using the management token returned when it was sent.
+    pub async fn withdraw(&self, id: &str, management_token: &str) -> Result<(), Error> {
+        let response = self
+            .http
+            .delete(format!("{}/api/message/{}", self.server, id))
+            .header("X-Management-Token", management_token)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(Error::NotFound);
+        }
+
+        response.error_for_status()?;
+        Ok(())
+    }
+
+    /// Delivery status of the notification email for a message.
+    pub async fn delivery_status(
+        &self,
+        id: &str,
+        management_token: &str,
+    ) -> Result<DeliveryStatus, Error> {
+        let response = self
+            .http
+            .get(format!("{}/api/delivery/{}", self.server, id))
+            .header("X-Management-Token", management_token)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(Error::NotFound);
+        }
+
+        Ok(response.error_for_status()?.json().await?)
+    }
+
+    /// Events recorded for a message, such as delivery of the notification and
+    /// the recipient opening it.
+    pub async fn message_status(
+        &self,
+        id: &str,
+        management_token: &str,
+    ) -> Result<MessageStatus, Error> {
+        let response = self
+            .http
+            .get(format!("{}/api/message/{}/status", self.server, id))
+            .header("X-Management-Token", management_token)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(Error::NotFound);
+        }
+
+        Ok(response.error_for_status()?.json().await?)
+    }
+
+    /// Report that a message was decrypted, which deletes it if it may only be
+    /// downloaded until then.
+    pub async fn confirm_decrypted(
+        &self,
+        id: &str,
+        access_secret: Option<&str>,
+    ) -> Result<(), Error> {
+        let mut request = self
+            .http
+            .post(format!("{}/api/download/{}/decrypted", self.server, id));
+        if let Some(secret) = access_secret {
+            request = request.header("X-Access-Secret", secret);
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+
+    /// Receipt signed by the recipient of a message, if any.
+    pub async fn receipt(&self, id: &str, management_token: &str) -> Result<Receipt, Error> {
+        let response = self
+            .http
+            .get(format!("{}/api/message/{}/receipt", self.server, id))
+            .header("X-Management-Token", management_token)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(Error::NotFound);
+        }
+
+        Ok(response.error_for_status()?.json().await?)
+    }
+
+    /// Fetch the sealed content of a message, `url` may be relative to the server.
+    pub async fn fetch_sealed(&self, url: &str) -> Result<SealedMessage, Error> {
+        let url = if url.starts_with('/') {
+            format!("{}{}", self.server, url)
+        } else {
+            url.to_owned()
+        };
+
+        Ok(self
+            .http
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    /// Start a session at the PKG, to be completed by disclosing the attribute.
+    pub async fn start_key_session(&self, attribute: &AttributeValue) -> Result<KeySession, Error> {
+        Ok(self
+            .http
+            .post(format!("{}/v1/request", self.pkg))
+            .json(&KeyRequest {
+                attribute: KeyAttribute {
+                    atype: &attribute.identifier.0,
+                    value: &attribute.value,
+                },
+            })
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    /// Fetch the user secret key of a session started at the PKG, for the given
+    /// message timestamp. Returns `None` while the session is still pending.
+    pub async fn session_key(
+        &self,
+        token: &str,
+        timestamp: u64,
+    ) -> Result<Option<UserSecretKey>, Error> {
+        self.session_url_key(&format!("{}/v1/request/{}", self.pkg, token), timestamp)
+            .await
+    }
+
+    /// As `session_key`, for a session url of the form `{pkg}/v1/request/{token}`.
+    pub async fn session_url_key(
+        &self,
+        session: &str,
+        timestamp: u64,
+    ) -> Result<Option<UserSecretKey>, Error> {
+        let response: KeyResponse = self
+            .http
+            .get(format!("{}/{}", session.trim_end_matches('/'), timestamp))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        match (response.status.as_str(), response.key) {
+            ("DONE_VALID", Some(key)) => Ok(Some(parse_user_secret_key(&key)?)),
+            ("DONE_VALID", None) | ("CANCELLED", _) | ("TIMEOUT", _) => Err(Error::Session),
+            _ => Ok(None),
+        }
+    }
+
+    /// Verify that the signature of a downloaded message covers the decrypted
+    /// plaintext, and was made by its sender.
+    pub async fn verify_signature(
+        &self,
+        verifier: &str,
+        metadata: &DownloadResult,
+        plain: &[u8],
+    ) -> Result<(), Error> {
+        let signature = metadata.signature.as_ref().ok_or(Error::InvalidSignature)?;
+        let sig_data: SigData = serde_json::from_str(signature)?;
+        if !common::is_signature_message(&sig_data.message, &common::message_hash(plain)) {
+            return Err(Error::InvalidSignature);
+        }
+
+        let attributes = self.verified_attributes(verifier, signature.clone()).await?;
+        if attributes.get(EMAIL_ATTRIBUTE_IDENTIFIER) != Some(&metadata.from) {
+            return Err(Error::InvalidSignature);
+        }
+
+        Ok(())
+    }
+
+    /// Verify that a receipt was signed by its recipient, for the given
+    /// sealed message.
+    pub async fn verify_receipt(
+        &self,
+        verifier: &str,
+        receipt: &Receipt,
+        sealed: &SealedMessage,
+    ) -> Result<(), Error> {
+        let hash = common::sealed_hash(sealed);
+        let sig_data: SigData = serde_json::from_value(receipt.signature.clone())?;
+        if receipt.hash != hash
+            || !common::is_receipt_message(&sig_data.message, &receipt.id, &hash, receipt.time)
+        {
+            return Err(Error::InvalidSignature);
+        }
+
+        let attributes = self
+            .verified_attributes(verifier, receipt.signature.to_string())
+            .await?;
+        if attributes.get(EMAIL_ATTRIBUTE_IDENTIFIER) != Some(&receipt.to) {
+            return Err(Error::InvalidSignature);
+        }
+
+        Ok(())
+    }
+
+    // Attributes disclosed in a signature, if the verifier accepts it
+    async fn verified_attributes(
+        &self,
+        verifier: &str,
+        signature: String,
+    ) -> Result<HashMap<String, String>, Error> {
+        let response = self
+            .http
+            .post(verifier)
+            .header("Content-Type", "application/json")
+            .body(signature)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(Error::InvalidSignature);
+        }
+
+        Ok(response.json().await?)
+    }
+
+    /// Default location of the signature verifier, next to the backend.
+    pub fn default_verifier(&self) -> String {
+        format!("{}/api/verify", self.server)
+    }
+}
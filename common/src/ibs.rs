@@ -0,0 +1,262 @@
+use core::convert::TryInto;
+use core::fmt::{Display, Formatter};
+
+use aes_gcm::aead::consts::U16;
+use aes_gcm::aead::{generic_array::GenericArray, Aead, NewAead};
+use aes_gcm::aes::Aes256;
+use aes_gcm::AesGcm;
+use ibe::kiltz_vahlis_one::{CipherText, PublicKey, SymmetricKey, UserSecretKey};
+use rand::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256};
+
+use crate::identity::derive_identity;
+use crate::{AttributeValue, RecipientMessage, SealedMessage};
+
+// AES-256-GCM with a 16 byte nonce, compatible with the messages sealed using WebCrypto
+type Cipher = AesGcm<Aes256, U16>;
+
+#[derive(Debug, PartialEq)]
+pub enum SealError {
+    InvalidPublicKey,
+    InvalidUserSecretKey,
+    InvalidIdentity,
+    Deserialize,
+    Failed,
+}
+
+impl Display for SealError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SealError::InvalidPublicKey => write!(f, "invalid public key"),
+            SealError::InvalidUserSecretKey => write!(f, "invalid user secret key"),
+            SealError::InvalidIdentity => write!(f, "attributes do not form a valid identity"),
+            SealError::Deserialize => write!(f, "failed to deserialize"),
+            SealError::Failed => write!(f, "encryption or decryption failed"),
+        }
+    }
+}
+
+impl std::error::Error for SealError {}
+
+/// Parse the base64 encoded public key as published by the PKG.
+pub fn parse_public_key(public_key: &str) -> Result<PublicKey, SealError> {
+    let data: [u8; 25056] = base64::decode(public_key)
+        .map_err(|_| SealError::InvalidPublicKey)?
+        .try_into()
+        .map_err(|_| SealError::InvalidPublicKey)?;
+    Option::from(PublicKey::from_bytes(&data)).ok_or(SealError::InvalidPublicKey)
+}
+
+/// Parse the base64 encoded user secret key as issued by the PKG.
+pub fn parse_user_secret_key(usk: &str) -> Result<UserSecretKey, SealError> {
+    let data: [u8; 192] = base64::decode(usk)
+        .map_err(|_| SealError::InvalidUserSecretKey)?
+        .try_into()
+        .map_err(|_| SealError::InvalidUserSecretKey)?;
+    Option::from(UserSecretKey::from_bytes(&data)).ok_or(SealError::InvalidUserSecretKey)
+}
+
+/// AES key for the symmetric key encapsulated by the IBE ciphertext.
+fn aes_key(k: &SymmetricKey) -> Vec<u8> {
+    Sha256::digest(&k.to_bytes()).to_vec()
+}
+
+/// Encrypt a message with a 32 byte AES key and a 16 byte nonce.
+fn encrypt(key: &[u8], iv: &[u8], message: &[u8]) -> Result<Vec<u8>, SealError> {
+    Cipher::new(GenericArray::from_slice(key))
+        .encrypt(GenericArray::from_slice(iv), message)
+        .map_err(|_| SealError::Failed)
+}
+
+/// Decrypt a message with a 32 byte AES key and a 16 byte nonce.
+fn decrypt(key: &[u8], iv: &[u8], ct: &[u8]) -> Result<Vec<u8>, SealError> {
+    Cipher::new(GenericArray::from_slice(key))
+        .decrypt(GenericArray::from_slice(iv), ct)
+        .map_err(|_| SealError::Failed)
+}
+
+/// Seal a message for a single recipient, identified by the given attributes.
+/// The timestamp (in seconds since the unix epoch) becomes part of the identity,
+/// so the recipient needs a user secret key issued for that same timestamp.
+pub fn seal<R: RngCore + CryptoRng>(
+    pk: &PublicKey,
+    to: &str,
+    attributes: &[AttributeValue],
+    message: &[u8],
+    timestamp: u64,
+    rng: &mut R,
+) -> Result<RecipientMessage, SealError> {
+    let identity = derive_identity(attributes, timestamp).ok_or(SealError::InvalidIdentity)?;
+    let (c, k) = ibe::kiltz_vahlis_one::encrypt(pk, &identity, rng);
+
+    let mut iv = [0u8; 16];
+    rng.fill_bytes(&mut iv);
+
+    let ct = encrypt(&aes_key(&k), &iv, message)?;
+
+    Ok(RecipientMessage {
+        to: to.to_owned(),
+        channel: Default::default(),
+        sender_copy: false,
+        sealed: SealedMessage {
+            iv: base64::encode(&iv),
+            ct: base64::encode(&ct),
+            c_key: base64::encode(&c.to_bytes()),
+            timestamp,
+            attributes: attributes.to_vec(),
+        },
+    })
+}
+
+/// Unseal a message using the user secret key for its identity.
+pub fn unseal(sm: &SealedMessage, usk: &UserSecretKey) -> Result<Vec<u8>, SealError> {
+    let c_key_data: [u8; 144] = base64::decode(&sm.c_key)
+        .map_err(|_| SealError::Deserialize)?
+        .try_into()
+        .map_err(|_| SealError::Deserialize)?;
+    let c_key: CipherText =
+        Option::from(CipherText::from_bytes(&c_key_data)).ok_or(SealError::Deserialize)?;
+
+    let k = ibe::kiltz_vahlis_one::decrypt(usk, &c_key);
+
+    let iv = base64::decode(&sm.iv).map_err(|_| SealError::Deserialize)?;
+    if iv.len() != 16 {
+        return Err(SealError::Deserialize);
+    }
+    let ct = base64::decode(&sm.ct).map_err(|_| SealError::Deserialize)?;
+
+    decrypt(&aes_key(&k), &iv, &ct)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decrypt, encrypt, seal, unseal, SealError};
+    use crate::identity::derive_identity;
+    use crate::{AttributeIdentifier, AttributeValue, SealedMessage};
+    use ibe::kiltz_vahlis_one::{extract_usk, setup};
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    fn attributes(value: &str) -> Vec<AttributeValue> {
+        vec![AttributeValue {
+            identifier: AttributeIdentifier("pbdf.sidn-pbdf.email.email".to_owned()),
+            value: value.to_owned(),
+        }]
+    }
+
+    #[test]
+    fn seal_unseal() {
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let (pk, sk) = setup(&mut rng);
+        let timestamp = 1629883307;
+        let attributes = attributes("to@example.com");
+
+        let message = seal(
+            &pk,
+            "to@example.com",
+            &attributes,
+            b"This is a test message",
+            timestamp,
+            &mut rng,
+        )
+        .unwrap();
+
+        assert_eq!(message.to, "to@example.com");
+        assert_eq!(message.sealed.timestamp, timestamp);
+        assert_eq!(message.sealed.attributes, attributes);
+
+        let identity = derive_identity(&attributes, timestamp).unwrap();
+        let usk = extract_usk(&pk, &sk, &identity, &mut rng);
+
+        assert_eq!(
+            unseal(&message.sealed, &usk).unwrap(),
+            b"This is a test message".to_vec()
+        );
+    }
+
+    #[test]
+    fn seal_is_deterministic_for_seeded_rng() {
+        let (pk, _) = setup(&mut ChaChaRng::seed_from_u64(42));
+        let attributes = attributes("to@example.com");
+
+        let first = seal(
+            &pk,
+            "to@example.com",
+            &attributes,
+            b"message",
+            1629883307,
+            &mut ChaChaRng::seed_from_u64(7),
+        )
+        .unwrap();
+        let second = seal(
+            &pk,
+            "to@example.com",
+            &attributes,
+            b"message",
+            1629883307,
+            &mut ChaChaRng::seed_from_u64(7),
+        )
+        .unwrap();
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn unseal_with_wrong_identity_fails() {
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let (pk, sk) = setup(&mut rng);
+        let timestamp = 1629883307;
+
+        let message = seal(
+            &pk,
+            "to@example.com",
+            &attributes("to@example.com"),
+            b"message",
+            timestamp,
+            &mut rng,
+        )
+        .unwrap();
+
+        let identity = derive_identity(&attributes("other@example.com"), timestamp).unwrap();
+        let usk = extract_usk(&pk, &sk, &identity, &mut rng);
+
+        assert_eq!(unseal(&message.sealed, &usk), Err(SealError::Failed));
+    }
+
+    /// Encrypted message as sent by the website, sealed for a PKG of which the
+    /// secret key is not available to the tests.
+    fn fixture() -> SealedMessage {
+        SealedMessage {
+            c_key: "h9J6WdqlnSgHEULkJbDJ1zBKjJ+LAWaTqEwlAUG5gA9GHT0S3I+0emOES7nfdzpOCEGqbfdDffMEFwqEiW7wGyR3NZJxSmM3GYwTJdZqNbTHosucrw+MsYctOdWdXHS9rfdQBtvlqUE1xYbCnrjsN4RHMpyUj2H+yHit70d0re5CIxUp0yArdidBz6LjUPpd".to_owned(),
+            ct: "gAMMKLikymhNIDeqUjqjJqEFTj8qWnrUUUhwCrIG6sOplxR4pFnUKA==".to_owned(),
+            iv: "0z6La7O6CfxcvND0LqDQBA==".to_owned(),
+            timestamp: 1629883307,
+            attributes: attributes("to@example.com"),
+        }
+    }
+
+    #[test]
+    fn known_answer() {
+        // AES key and ciphertext computed independently with AES-256-GCM, using
+        // the 16 byte nonce of the fixture, as WebCrypto does
+        let key = "6bd6263180bf5b96706dc42af06fa89b752dd0d492b7c43f7b6446650ff3d318";
+        let key = hex::decode(key).unwrap();
+        let iv = base64::decode(fixture().iv).unwrap();
+        let ct = base64::decode("2WIMjdqlXzu8c69r6S9wxZmMDjrRajIw5XvWT2CHuQaIxxwGBP8=").unwrap();
+
+        assert_eq!(encrypt(&key, &iv, b"This is a test message").unwrap(), ct);
+        assert_eq!(decrypt(&key, &iv, &ct).unwrap(), b"This is a test message".to_vec());
+        assert_eq!(decrypt(&key, &iv, &ct[1..]), Err(SealError::Failed));
+    }
+
+    #[test]
+    fn unseal_fixture() {
+        // The fixture deserializes, but only the recipient can decrypt it
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let (pk, sk) = setup(&mut rng);
+        let identity = derive_identity(&attributes("to@example.com"), 1629883307).unwrap();
+        let usk = extract_usk(&pk, &sk, &identity, &mut rng);
+
+        assert_eq!(unseal(&fixture(), &usk), Err(SealError::Failed));
+    }
+}
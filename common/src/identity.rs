@@ -0,0 +1,29 @@
+use core::convert::TryFrom;
+
+use arrayvec::ArrayVec;
+use ibe::kiltz_vahlis_one::Identity;
+
+use crate::AttributeValue;
+
+/// Derive the IBE identity a message is sealed for, as the PKG does when issuing
+/// a user secret key for an attribute value at a given timestamp.
+pub fn derive_identity(attributes: &[AttributeValue], timestamp: u64) -> Option<Identity> {
+    let mut buf = ArrayVec::<u8, 1024>::new();
+
+    buf.try_extend_from_slice(&timestamp.to_be_bytes()).ok()?;
+
+    // TODO use all attributes
+    let attribute = attributes.get(0)?;
+
+    let at = attribute.identifier.0.as_bytes();
+    let at_len = u8::try_from(at.len()).ok()?;
+    buf.try_extend_from_slice(&[at_len]).ok()?;
+    buf.try_extend_from_slice(at).ok()?;
+
+    let av = attribute.value.as_bytes();
+    let av_len = u8::try_from(av.len()).ok()?;
+    buf.try_extend_from_slice(&[av_len]).ok()?;
+    buf.try_extend_from_slice(av).ok()?;
+
+    Some(Identity::derive(&buf))
+}
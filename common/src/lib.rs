@@ -1,7 +1,12 @@
+pub mod ibs;
+pub mod identity;
+pub mod mime;
+
 use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
-use validator::Validate;
+use sha3::{Digest, Sha3_512};
+use validator::{validate_email, Validate, ValidationError};
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct AttributeIdentifier(pub String);
@@ -14,7 +19,185 @@ pub struct DownloadResult {
     pub subject: String,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub signature: Option<String>,
+    /// Attributes and timestamp the message is sealed for, needed to obtain a
+    /// key before fetching the content
+    pub attributes: Vec<AttributeValue>,
+    pub timestamp: u64,
     pub content: String,
+    /// Remaining downloads of the content, the message is deleted afterwards
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_limit: Option<DownloadLimit>,
+}
+
+/// Limits how often the content of a message can be fetched, once the limit
+/// runs out the message is deleted.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadLimit {
+    /// The content can be fetched this many times
+    Count(u32),
+    /// The content can be fetched until the recipient decrypted it
+    UntilDecrypted,
+}
+
+/// Body of the 410 response for a message whose download limit ran out, which
+/// tells it apart from a message withdrawn by its sender.
+pub const EXHAUSTED: &str = "This message can no longer be downloaded";
+
+/// Body of the 400 response for a submission whose encrypted messages exceed
+/// the size the backend accepts.
+pub const TOO_LARGE: &str = "Encrypted data too large";
+
+/// Language of the emails and pages shown to a recipient, and of the text the
+/// sender signs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    Nl,
+    En,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::Nl, Locale::En];
+
+    /// Language code, as used in urls and template directories.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Locale::Nl => "nl",
+            Locale::En => "en",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Locale> {
+        Locale::ALL
+            .iter()
+            .copied()
+            .find(|locale| code.eq_ignore_ascii_case(locale.code()))
+    }
+
+    /// Locale for a recipient when the sender did not choose one, Dutch for
+    /// addresses in the `.nl` domain and Dutch phone numbers, and English
+    /// otherwise.
+    pub fn for_address(address: &str) -> Locale {
+        let domain = address.rsplit('@').next().unwrap_or_default();
+        if domain.to_ascii_lowercase().ends_with(".nl") || address.starts_with("+31") {
+            Locale::Nl
+        } else {
+            Locale::En
+        }
+    }
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale::Nl
+    }
+}
+
+/// Channel through which a recipient is told about a new message.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    /// Email to the address in `to`
+    Email,
+    /// Text message to the phone number in `to`, in international format
+    Sms,
+    /// Request to the webhook with this name configured in the backend, `to`
+    /// identifies the recipient in the receiving system
+    Webhook(String),
+}
+
+impl Channel {
+    /// Code of the channel as stored by the backend, `email`, `sms` or
+    /// `webhook:{name}`.
+    pub fn code(&self) -> String {
+        match self {
+            Channel::Email => "email".to_owned(),
+            Channel::Sms => "sms".to_owned(),
+            Channel::Webhook(name) => format!("webhook:{}", name),
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Channel> {
+        match code {
+            "email" => Some(Channel::Email),
+            "sms" => Some(Channel::Sms),
+            _ => code
+                .strip_prefix("webhook:")
+                .map(|name| Channel::Webhook(name.to_owned())),
+        }
+    }
+
+    pub fn is_email(&self) -> bool {
+        *self == Channel::Email
+    }
+}
+
+impl Default for Channel {
+    fn default() -> Self {
+        Channel::Email
+    }
+}
+
+/// Whether the number is a phone number in international format, such as
+/// `+31612345678`.
+pub fn is_phone_number(number: &str) -> bool {
+    match number.strip_prefix('+') {
+        Some(digits) => {
+            (8..=15).contains(&digits.len()) && digits.chars().all(|c| c.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+// The address of a recipient has to fit the channel it is notified through
+fn validate_recipient(message: &RecipientMessage) -> Result<(), ValidationError> {
+    let valid = match &message.channel {
+        Channel::Email => validate_email(&message.to),
+        Channel::Sms => is_phone_number(&message.to),
+        Channel::Webhook(name) => !name.is_empty() && !message.to.is_empty(),
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(ValidationError::new("to"))
+    }
+}
+
+// A copy for the sender is emailed to the sender's own address, there is at
+// most one, and only along with a message for another recipient
+fn validate_sender_copy(data: &MessageData) -> Result<(), ValidationError> {
+    let mut copies = data
+        .recipient_messages
+        .iter()
+        .filter(|message| message.sender_copy);
+    let recipients = data
+        .recipient_messages
+        .iter()
+        .any(|message| !message.sender_copy);
+    let valid = match (copies.next(), copies.next()) {
+        (None, _) => true,
+        (Some(copy), None) => {
+            recipients && copy.channel.is_email() && copy.to.eq_ignore_ascii_case(&data.from)
+        }
+        _ => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(ValidationError::new("sender_copy"))
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+fn validate_download_limit(limit: &DownloadLimit) -> Result<(), ValidationError> {
+    match limit {
+        DownloadLimit::Count(0) => Err(ValidationError::new("download_limit")),
+        _ => Ok(()),
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Validate)]
@@ -38,14 +221,24 @@ pub struct SealedMessage {
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "validate_recipient"))]
 pub struct RecipientMessage {
-    #[validate(email)]
+    /// Address of the recipient for its channel
+    #[validate(length(max = 512))]
     pub to: String,
+    #[serde(default, skip_serializing_if = "Channel::is_email")]
+    pub channel: Channel,
+    /// Copy of the message for the sender, sealed for their own email address
+    /// so they can read it again later. It is not counted as a recipient, and
+    /// only accepted along with a message for another recipient.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub sender_copy: bool,
     #[validate]
     pub sealed: SealedMessage,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "validate_sender_copy"))]
 pub struct MessageData {
     #[validate(email)]
     pub from: String,
@@ -56,6 +249,83 @@ pub struct MessageData {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     #[validate(length(max = 4096))]
     pub signature: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(custom = "validate_download_limit")]
+    pub download_limit: Option<DownloadLimit>,
+    /// Language for all recipients, derived from each recipient's address when
+    /// not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<Locale>,
+    /// Only send the download link in notification emails, instead of also
+    /// attaching the encrypted message
+    #[serde(default)]
+    pub link_only: bool,
+    /// Do not notify the recipients, the download links are returned instead
+    /// so the sender can hand them over
+    #[serde(default)]
+    pub share_link: bool,
+}
+
+/// Message as sent to a single recipient.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SentMessage {
+    pub id: String,
+    pub to: String,
+    /// Secret which allows the sender to manage the message, it is only
+    /// returned once
+    pub management_token: String,
+    /// Secret which allows the recipient to download the message, it is part
+    /// of the download link and only returned once
+    pub access_secret: String,
+    /// Download link for the recipient, only returned for shared links
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Whether this is the copy for the sender
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub sender_copy: bool,
+}
+
+/// Response to a submitted message, which is accepted for all recipients or
+/// rejected as a whole.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SendResult {
+    pub accepted: Vec<SentMessage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct DeliveryStatus {
+    /// One of `pending`, `sent`, `failed` or `bounced`
+    pub status: String,
+    pub attempts: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MessageEvent {
+    /// One of `notification_sent`, `notification_bounced`, `page_fetched`,
+    /// `content_fetched` or `receipt_signed`
+    pub event: String,
+    /// Seconds since the unix epoch
+    pub time: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MessageStatus {
+    pub id: String,
+    pub to: String,
+    pub events: Vec<MessageEvent>,
+}
+
+/// Signed acknowledgement that the recipient received and opened a message.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Receipt {
+    pub id: String,
+    pub to: String,
+    /// Hash of the sealed message, see `sealed_hash`
+    pub hash: String,
+    /// Seconds since the unix epoch at which the receipt was signed
+    pub time: u64,
+    /// IRMA signature over the text produced by `receipt_message`
+    pub signature: serde_json::Value,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -63,3 +333,170 @@ pub struct SignResult {
     pub signature: serde_json::Value,
     pub attributes: HashMap<String, String>,
 }
+
+/// Hash of a plaintext message, as covered by the sender's signature.
+pub fn message_hash(message: &[u8]) -> String {
+    hex::encode(Sha3_512::digest(message))
+}
+
+/// Hash of a sealed message, as covered by the recipient's receipt. The
+/// backend can compute it from the stored message without decrypting it.
+pub fn sealed_hash(sealed: &SealedMessage) -> String {
+    let encoded = serde_json::to_vec(sealed).expect("sealed messages can always be serialized");
+    message_hash(&encoded)
+}
+
+/// Text the sender signs using IRMA, for a message with the given hash.
+pub fn signature_message(hash: &str, locale: Locale) -> String {
+    match locale {
+        Locale::Nl => format!("Tguard bericht met hash {}", hash),
+        Locale::En => format!("Tguard message with hash {}", hash),
+    }
+}
+
+/// Whether `message` is the signed text for a message with the given hash, in
+/// any of the supported languages.
+pub fn is_signature_message(message: &str, hash: &str) -> bool {
+    Locale::ALL
+        .iter()
+        .any(|locale| message == signature_message(hash, *locale))
+}
+
+/// Text the recipient signs using IRMA to acknowledge receiving a message.
+pub fn receipt_message(id: &str, hash: &str, time: u64, locale: Locale) -> String {
+    match locale {
+        Locale::Nl => format!(
+            "Tguard ontvangstbevestiging voor bericht {} met hash {} op tijdstip {}",
+            id, hash, time
+        ),
+        Locale::En => format!(
+            "Tguard receipt for message {} with hash {} at time {}",
+            id, hash, time
+        ),
+    }
+}
+
+/// Whether `message` is the signed receipt text for the given message, in any
+/// of the supported languages.
+pub fn is_receipt_message(message: &str, id: &str, hash: &str, time: u64) -> bool {
+    Locale::ALL
+        .iter()
+        .any(|locale| message == receipt_message(id, hash, time, *locale))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        is_phone_number, is_receipt_message, is_signature_message, receipt_message, sealed_hash,
+        signature_message, Channel, Locale, MessageData, RecipientMessage, SealedMessage,
+    };
+    use validator::Validate;
+
+    #[test]
+    fn locale_for_address() {
+        assert_eq!(Locale::for_address("jan@example.NL"), Locale::Nl);
+        assert_eq!(Locale::for_address("jane@example.com"), Locale::En);
+        assert_eq!(Locale::for_address("+31612345678"), Locale::Nl);
+        assert_eq!(Locale::for_address("+447700900123"), Locale::En);
+        assert_eq!(Locale::from_code("EN"), Some(Locale::En));
+        assert_eq!(Locale::from_code("de"), None);
+    }
+
+    #[test]
+    fn signature_message_any_locale() {
+        let message = signature_message("abc", Locale::En);
+        assert!(is_signature_message(&message, "abc"));
+        assert!(!is_signature_message(&message, "abd"));
+    }
+
+    #[test]
+    fn receipt_message_any_locale() {
+        for locale in Locale::ALL.iter() {
+            let message = receipt_message("id", "abc", 1629883307, *locale);
+            assert!(is_receipt_message(&message, "id", "abc", 1629883307));
+            assert!(!is_receipt_message(&message, "id", "abc", 1629883308));
+        }
+        assert_ne!(
+            receipt_message("id", "abc", 1629883307, Locale::Nl),
+            receipt_message("id", "abc", 1629883307, Locale::En)
+        );
+    }
+
+    #[test]
+    fn sealed_hash_survives_storage() {
+        let sealed = SealedMessage {
+            iv: "0z6La7O6CfxcvND0LqDQBA==".to_owned(),
+            ct: "gAMMKLikymhNIDeqUjqjJqEFTj8qWnrUUUhwCrIG6sOplxR4pFnUKA==".to_owned(),
+            c_key: "h9J6WdqlnSgHEULkJbDJ1zBKjJ+LAWaT".to_owned(),
+            timestamp: 1629883307,
+            attributes: vec![],
+        };
+        let stored: SealedMessage =
+            serde_json::from_slice(&serde_json::to_vec(&sealed).unwrap()).unwrap();
+        assert_eq!(sealed_hash(&stored), sealed_hash(&sealed));
+
+        let other = SealedMessage {
+            ct: "hAMMKLikymhNIDeqUjqjJqEFTj8qWnrUUUhwCrIG6sOplxR4pFnUKA==".to_owned(),
+            ..sealed.clone()
+        };
+        assert_ne!(sealed_hash(&other), sealed_hash(&sealed));
+    }
+
+    #[test]
+    fn channel_codes() {
+        for channel in [
+            Channel::Email,
+            Channel::Sms,
+            Channel::Webhook("chat".to_owned()),
+        ] {
+            assert_eq!(Channel::from_code(&channel.code()), Some(channel));
+        }
+        assert_eq!(Channel::from_code("fax"), None);
+    }
+
+    #[test]
+    fn phone_numbers() {
+        assert!(is_phone_number("+31612345678"));
+        assert!(!is_phone_number("0612345678"));
+        assert!(!is_phone_number("+31 6 12345678"));
+        assert!(!is_phone_number("+316"));
+    }
+
+    #[test]
+    fn sender_copy() {
+        let recipient = |to: &str, sender_copy: bool| RecipientMessage {
+            to: to.to_owned(),
+            channel: Channel::Email,
+            sender_copy,
+            sealed: SealedMessage {
+                iv: "0z6La7O6CfxcvND0LqDQBA==".to_owned(),
+                ct: "gAMMKLikymhNIDeqUjqjJqEFTj8qWnrUUUhwCrIG6sOplxR4pFnUKA==".to_owned(),
+                c_key: "h9J6WdqlnSgHEULkJbDJ1zBKjJ+LAWaT".to_owned(),
+                timestamp: 1629883307,
+                attributes: vec![],
+            },
+        };
+        let data = |recipient_messages| MessageData {
+            from: "from@example.com".to_owned(),
+            subject: "Example subject".to_owned(),
+            recipient_messages,
+            signature: None,
+            download_limit: None,
+            locale: None,
+            link_only: false,
+            share_link: false,
+        };
+
+        let to = recipient("to@example.com", false);
+        let copy = recipient("From@example.com", true);
+        assert!(data(vec![to.clone(), copy.clone()]).validate().is_ok());
+        assert!(data(vec![to.clone(), copy.clone(), copy.clone()]).validate().is_err());
+        assert!(data(vec![copy]).validate().is_err());
+        assert!(data(vec![recipient("to@example.com", true)]).validate().is_err());
+        let sms = RecipientMessage {
+            channel: Channel::Sms,
+            ..recipient("from@example.com", true)
+        };
+        assert!(data(vec![to, sms]).validate().is_err());
+    }
+}
@@ -0,0 +1,65 @@
+use std::iter::repeat_with;
+
+use lettre::message::{header::ContentType, Attachment as MimeAttachment, MultiPart, SinglePart};
+use rand::{distributions::Alphanumeric, Rng};
+
+/// File attached to a message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attachment {
+    pub name: String,
+    pub content_type: String,
+    pub content: Vec<u8>,
+}
+
+/// Pack the text of a message and its attachments into a MIME message, which
+/// is what gets sealed for the recipients. Returns `None` when the content type
+/// of an attachment is invalid.
+pub fn convert_to_mime<R: Rng>(
+    rng: &mut R,
+    text: &str,
+    attachments: &[Attachment],
+) -> Option<String> {
+    // The boundary is set here, since the one lettre generates relies on
+    // fastrand, which does not work in the browser
+    let boundary: String = repeat_with(|| rng.sample(Alphanumeric)).take(40).collect();
+    let content_type = format!("multipart/mixed; boundary={}", boundary);
+    let mut content = MultiPart::builder()
+        .header(ContentType::parse(&content_type).ok()?)
+        .singlepart(SinglePart::plain(text.to_owned()));
+
+    for attachment in attachments {
+        let content_type = ContentType::parse(&attachment.content_type).ok()?;
+        let part = MimeAttachment::new(attachment.name.clone())
+            .body(attachment.content.clone(), content_type);
+        content = content.singlepart(part);
+    }
+
+    String::from_utf8(content.formatted()).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{convert_to_mime, Attachment};
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    #[test]
+    fn packed_message() {
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let attachment = Attachment {
+            name: "notes.txt".to_owned(),
+            content_type: "text/plain".to_owned(),
+            content: b"Some notes".to_vec(),
+        };
+        let packed = convert_to_mime(&mut rng, "Hello", &[attachment.clone()]).unwrap();
+        assert!(packed.contains("Content-Type: multipart/mixed; boundary="));
+        assert!(packed.contains("Hello"));
+        assert!(packed.contains("filename=\"notes.txt\""));
+
+        let invalid = Attachment {
+            content_type: "not a content type".to_owned(),
+            ..attachment
+        };
+        assert_eq!(convert_to_mime(&mut rng, "Hello", &[invalid]), None);
+    }
+}
@@ -1,29 +1,3 @@
-export async function encrypt(message, key, iv) {
-  try {
-    const keyHash = await crypto.subtle.digest('SHA-256', key);
-    const aesKey = await window.crypto.subtle.importKey(
-      'raw',
-      keyHash,
-      'AES-GCM',
-      true,
-      ['encrypt']
-    );
-    const ct = await window.crypto.subtle.encrypt(
-      {
-        name: 'AES-GCM',
-        iv,
-      },
-      aesKey,
-      (new TextEncoder()).encode(message),
-    );
-
-    return new Uint8Array(ct);
-  } catch (e) {
-    console.error(e);
-    return null;
-  }
-}
-
 export async function irma_get_usk(session) {
   const identity = { type: session.attribute_identifier, value: session.attribute_value };
 
@@ -32,7 +6,7 @@ export async function irma_get_usk(session) {
       identity,
       timestamp: session.timestamp,
       maxAge: 300,
-      url: 'https://irmacrypt.nl/pkg',
+      url: session.pkg_url,
       start: {
         url: (o) => `${o.url}/v1/request`,
         method: 'POST',
@@ -58,7 +32,7 @@ export async function irma_get_usk(session) {
   }
 }
 
-export async function irma_sign(hash) {
+export async function irma_sign(hash, locale) {
   try {
     const signature = await window.startIrma({
       maxAge: 300,
@@ -69,6 +43,7 @@ export async function irma_sign(hash) {
         body: JSON.stringify({
          hash,
          attributes: ["pbdf.sidn-pbdf.email.email"],
+         locale,
         }),
       },
       state: { serverSentEvents: false },
@@ -88,26 +63,33 @@ export async function irma_sign(hash) {
   }
 }
 
-export async function decrypt(ciphertext, key, iv) {
+export async function irma_sign_receipt(id, hash, time, locale) {
   try {
-    const keyHash = await crypto.subtle.digest('SHA-256', key);
-    const aesKey = await window.crypto.subtle.importKey(
-      'raw',
-      keyHash,
-      'AES-GCM',
-      true,
-      ['decrypt']
-    );
-    const encoded = await window.crypto.subtle.decrypt(
-      {
-        name: 'AES-GCM',
-        iv,
+    const receipt = await window.startIrma({
+      maxAge: 300,
+      start: {
+        url: (o) => `${o.url}/api/sign`,
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({
+         hash,
+         attributes: ["pbdf.sidn-pbdf.email.email"],
+         receipt: { id, time },
+         locale,
+        }),
       },
-      aesKey,
-      ciphertext
-    );
+      state: { serverSentEvents: false },
+      mapping: {
+        sessionPtr: (r) => r.sessionPtr,
+      },
+      result: {
+        url: (o, { sessionToken }) => `${o.url}/api/message/${id}/receipt?session=${sessionToken}&hash=${hash}&time=${time}`,
+        method: 'POST',
+        parseResponse: (r) => r.text(),
+      },
+    });
 
-    return (new TextDecoder()).decode(encoded).toString();
+    return receipt;
   } catch (e) {
     console.error(e);
     return null;
@@ -140,3 +122,13 @@ export async function decrypt_cfb_hmac(ciphertext, key, iv) {
   }
 }
 
+
+export async function copy_text(text) {
+  try {
+    await navigator.clipboard.writeText(text);
+    return true;
+  } catch (e) {
+    console.error(e);
+    return false;
+  }
+}
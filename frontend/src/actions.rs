@@ -1,23 +1,25 @@
 use serde::Deserialize;
-use sha3::{Digest, Sha3_512};
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 use wasm_bindgen::prelude::JsValue;
 use yew::prelude::ComponentLink;
 
 use common::{
-    AttributeValue, DownloadResult, MessageData, RecipientMessage, SealedMessage, SignResult,
+    is_signature_message, message_hash, sealed_hash, AttributeValue, DownloadResult, Locale,
+    MessageData, Receipt, RecipientMessage, SealedMessage, SignResult,
 };
 
 use crate::components::receive_form::{ReceiveForm, ReceiveFormMsg};
 use crate::components::send_form::{SendForm, SendFormMsg, SendFormStatus};
+use crate::i18n::{self, Text, Translate};
 use crate::ibs::seal;
 use crate::ibs::unseal;
 use crate::js_functions::{
-    download, download_bytes, get_public_key, irma_get_usk, irma_sign, send_message,
-    verify_signature, IrmaSession,
+    access_secret, confirm_decrypted, download, download_bytes, get_public_key, irma_get_usk,
+    irma_sign, irma_sign_receipt, send_message, verify_signature, Download, IrmaSession, PKG_URL,
 };
 use crate::mime::convert_from_mime;
+use crate::sent::store_sent;
 use crate::types::{FormData, ReceivedData};
 
 #[derive(Debug, PartialEq)]
@@ -28,31 +30,35 @@ pub enum SendError {
     SerializeError,
     NotSent,
     TooLarge,
+    RateLimited,
 }
 
 impl Display for SendError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            SendError::MissingKey => write!(f, "public key not found"),
-            SendError::SignError => write!(f, "sign failed"),
-            SendError::FailedSeal => write!(f, "failed to encrypt the message"),
-            SendError::SerializeError => write!(f, "failed to serialize the message"),
-            SendError::NotSent => write!(f, "message could not be sent"),
-            SendError::TooLarge => write!(
-                f,
-                "the message is too large, please limit the total size to 1 MB"
-            ),
-        }
+        let text = match self {
+            SendError::MissingKey => Text::PublicKeyNotFound,
+            SendError::SignError => Text::SignFailed,
+            SendError::FailedSeal => Text::EncryptFailed,
+            SendError::SerializeError => Text::SerializeFailed,
+            SendError::NotSent => Text::NotSent,
+            SendError::TooLarge => Text::TooLarge,
+            SendError::RateLimited => Text::RateLimited,
+        };
+        write!(f, "{}", i18n::current().t(text))
     }
 }
 
 impl Error for SendError {}
 
-pub async fn sign(link: &ComponentLink<SendForm>, message: &str) -> Result<String, SendError> {
+pub async fn sign(
+    link: &ComponentLink<SendForm>,
+    message: &str,
+    locale: Locale,
+) -> Result<String, SendError> {
     link.send_message(SendFormMsg::UpdateStatus(SendFormStatus::Encrypting));
-    let hash = hex::encode(Sha3_512::digest(message));
+    let hash = message_hash(message.as_bytes());
 
-    let signature = irma_sign(hash)
+    let signature = irma_sign(hash, locale.code().to_owned())
         .await
         .as_string()
         .ok_or(SendError::SignError)?;
@@ -74,47 +80,64 @@ pub async fn encrypt_and_submit(
 
     let public_key: String = get_public_key().await.ok_or(SendError::MissingKey)?;
 
-    let sms: Vec<RecipientMessage> = seal(public_key, &form, message)
-        .await
-        .ok_or(SendError::FailedSeal)?;
+    let sms: Vec<RecipientMessage> =
+        seal(public_key, &form, message).ok_or(SendError::FailedSeal)?;
 
     let data = MessageData {
         from: form.from.clone(),
         subject: form.subject.clone(),
         recipient_messages: sms,
         signature,
+        download_limit: form.download_limit,
+        locale: form.locale,
+        link_only: form.link_only,
+        share_link: form.share_link,
     };
 
     let json = serde_json::to_string(&data).map_err(|_| SendError::SerializeError)?;
 
-    send_message(&json).await?;
+    let result = send_message(&json).await?;
+    store_sent(&form.subject, &result);
 
-    link.send_message(SendFormMsg::UpdateStatus(SendFormStatus::Sent));
+    link.send_message(SendFormMsg::UpdateStatus(SendFormStatus::Sent(result)));
 
     Ok(())
 }
 
-pub async fn decrypt_message(message: &SealedMessage) -> Option<String> {
+async fn get_usk(attributes: &[AttributeValue], timestamp: u64) -> Option<String> {
     // TODO here comes multiple attribute support
-    let attribute: AttributeValue = message.attributes[0].clone();
+    let attribute: &AttributeValue = attributes.get(0)?;
 
     let irma_session = IrmaSession {
         attribute_identifier: attribute.identifier.0.clone(),
         attribute_value: attribute.value.to_owned(),
-        timestamp: message.timestamp,
+        timestamp,
+        pkg_url: PKG_URL.to_owned(),
     };
 
-    let usk = irma_get_usk(JsValue::from_serde(&irma_session).ok()?)
+    irma_get_usk(JsValue::from_serde(&irma_session).ok()?)
         .await
-        .as_string()?;
+        .as_string()
+}
 
-    unseal(message, usk).await
+pub async fn decrypt_message(message: &SealedMessage) -> Option<String> {
+    let usk = get_usk(&message.attributes, message.timestamp).await?;
+    unseal(message, usk)
 }
 
 pub async fn download_and_decrypt(link: &ComponentLink<ReceiveForm>, id: &str) -> Option<()> {
-    let message_metadata: DownloadResult = download(id).await?;
-    let message_data = download_bytes(&message_metadata.content).await?;
-    let message = serde_json::from_slice::<SealedMessage>(&message_data).ok()?;
+    let secret = access_secret();
+    let message_metadata: DownloadResult = match download(id, secret.as_deref()).await? {
+        Download::Found(message_metadata) => message_metadata,
+        Download::Withdrawn => {
+            link.send_message(ReceiveFormMsg::Withdrawn);
+            return Some(());
+        }
+        Download::Exhausted => {
+            link.send_message(ReceiveFormMsg::Exhausted);
+            return Some(());
+        }
+    };
 
     link.send_message(ReceiveFormMsg::Update(ReceivedData {
         from: message_metadata.from.clone(),
@@ -122,12 +145,24 @@ pub async fn download_and_decrypt(link: &ComponentLink<ReceiveForm>, id: &str) -
         subject: message_metadata.subject.clone(),
         message: Default::default(),
         attachments: vec![],
-        attributes: message.attributes.clone(),
+        attributes: message_metadata.attributes.clone(),
         signed: false,
+        hash: Default::default(),
+        download_limit: message_metadata.download_limit,
     }));
 
-    let pt = decrypt_message(&message).await?;
+    // The content is only fetched once the recipient obtained a key, so the
+    // sender sees the message as downloaded when it is actually read
+    let usk = get_usk(&message_metadata.attributes, message_metadata.timestamp).await?;
+    let message_data = download_bytes(&message_metadata.content).await?;
+    let message = serde_json::from_slice::<SealedMessage>(&message_data).ok()?;
+    let pt = unseal(&message, usk)?;
+    let receipt_hash = sealed_hash(&message);
+    if message_metadata.download_limit.is_some() {
+        confirm_decrypted(id, secret.as_deref()).await?;
+    }
 
+    let hash = message_hash(pt.as_bytes());
     let mut signed = false;
     // Check signature if present
     if let Some(signature) = &message_metadata.signature {
@@ -136,12 +171,7 @@ pub async fn download_and_decrypt(link: &ComponentLink<ReceiveForm>, id: &str) -
             message: String,
         }
         let sig_data: SigData = serde_json::from_str(signature).ok()?;
-        if sig_data.message
-            != format!(
-                "Tguard bericht met hash {}",
-                hex::encode(Sha3_512::digest(&pt))
-            )
-        {
+        if !is_signature_message(&sig_data.message, &hash) {
             return None;
         }
 
@@ -165,7 +195,19 @@ pub async fn download_and_decrypt(link: &ComponentLink<ReceiveForm>, id: &str) -
         attachments: converted.1,
         attributes: message.attributes,
         signed,
+        hash: receipt_hash,
+        download_limit: message_metadata.download_limit,
     }));
 
     Some(())
 }
+
+pub async fn sign_receipt(id: &str, hash: &str, locale: Locale) -> Option<Receipt> {
+    let time = (js_sys::Date::now() / 1000.0) as u64;
+    let locale = locale.code().to_owned();
+    let receipt = irma_sign_receipt(id.to_owned(), hash.to_owned(), time, locale)
+        .await
+        .as_string()?;
+
+    serde_json::from_str(&receipt).ok()
+}
@@ -1,4 +1,6 @@
-use common::AttributeIdentifier;
+use common::{AttributeIdentifier, Channel, Locale};
+
+use crate::i18n::{Text, Translate};
 
 #[allow(dead_code)]
 #[derive(Clone, PartialEq)]
@@ -11,7 +13,7 @@ pub enum AttributeType {
 pub const EMAIL_ATTRIBUTE_IDENTIFIER: &str = "pbdf.sidn-pbdf.email.email";
 const NAME_ATTRIBUTE_IDENTIFIER: &str = "pbdf.gemeente.personalData.fullname";
 const IBAN_ATTRIBUTE_IDENTIFIER: &str = "pbdf.pbdf.ideal.iban";
-const PHONE_NUMBER_ATTRIBUTE_IDENTIFIER: &str = "pbdf.sidn-pbdf.mobilenumber.mobilenumber";
+pub const PHONE_NUMBER_ATTRIBUTE_IDENTIFIER: &str = "pbdf.sidn-pbdf.mobilenumber.mobilenumber";
 
 pub const ALLOWED_ATTRIBUTES: [&str; 4] = [
     EMAIL_ATTRIBUTE_IDENTIFIER,
@@ -20,17 +22,26 @@ pub const ALLOWED_ATTRIBUTES: [&str; 4] = [
     PHONE_NUMBER_ATTRIBUTE_IDENTIFIER,
 ];
 
-pub fn attribute_label(identifier: &AttributeIdentifier) -> &str {
+pub fn attribute_label(identifier: &AttributeIdentifier, locale: Locale) -> &str {
     let ident = identifier.0.as_ref();
     match ident {
-        EMAIL_ATTRIBUTE_IDENTIFIER => "Email",
-        NAME_ATTRIBUTE_IDENTIFIER => "Full name",
-        IBAN_ATTRIBUTE_IDENTIFIER => "IBAN",
-        PHONE_NUMBER_ATTRIBUTE_IDENTIFIER => "Phone number",
+        EMAIL_ATTRIBUTE_IDENTIFIER => locale.t(Text::Email),
+        NAME_ATTRIBUTE_IDENTIFIER => locale.t(Text::FullName),
+        IBAN_ATTRIBUTE_IDENTIFIER => locale.t(Text::Iban),
+        PHONE_NUMBER_ATTRIBUTE_IDENTIFIER => locale.t(Text::PhoneNumber),
         s => s,
     }
 }
 
+/// Attribute the address of a recipient is, which the message is sealed for
+/// in the email column of the form.
+pub fn address_attribute(channel: &Channel) -> &'static str {
+    match channel {
+        Channel::Sms => PHONE_NUMBER_ATTRIBUTE_IDENTIFIER,
+        _ => EMAIL_ATTRIBUTE_IDENTIFIER,
+    }
+}
+
 pub fn attribute_type(_identifier: &AttributeIdentifier) -> AttributeType {
     AttributeType::String
 }
@@ -3,4 +3,5 @@ pub mod index;
 pub mod layout;
 pub mod receive_form;
 pub mod send_form;
+pub mod sent_messages;
 pub mod upload;
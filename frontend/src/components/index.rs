@@ -1,7 +1,10 @@
 use crate::components::layout::Layout;
 use crate::components::receive_form::ReceiveForm;
 use crate::components::send_form::SendForm;
+use crate::components::sent_messages::SentMessages;
 use crate::components::upload::Upload;
+use crate::i18n;
+use common::Locale;
 use yew::prelude::{html, Component, ComponentLink, Html, ShouldRender};
 use yew_router::prelude::{Router, Switch};
 
@@ -9,25 +12,47 @@ use yew_router::prelude::{Router, Switch};
 pub enum AppRoute {
     #[to = "/upload"]
     Upload,
+    #[to = "/sent"]
+    Sent,
+    // Download links in notification emails include the recipient's language
+    #[to = "/{lang}/download/{id}"]
+    DecryptLocalized(String, String),
     #[to = "/download/{id}"]
     Decrypt(String),
     #[to = "/"]
     Encrypt,
 }
 
-#[derive(Debug)]
-pub struct Index;
+pub enum IndexMsg {
+    SetLocale(Locale),
+}
+
+/// Root of the app, keeps the language the interface is shown in.
+pub struct Index {
+    link: ComponentLink<Self>,
+    locale: Locale,
+}
 
 impl Component for Index {
     type Properties = ();
-    type Message = ();
+    type Message = IndexMsg;
 
-    fn create(_props: Self::Properties, _link: ComponentLink<Self>) -> Self {
-        Self
+    fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let locale = i18n::detect();
+        i18n::set_current(locale);
+        Self { link, locale }
     }
 
-    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
-        false
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Self::Message::SetLocale(locale) => {
+                i18n::set_current(locale);
+                i18n::remember(locale);
+                self.locale = locale;
+            }
+        }
+
+        true
     }
 
     fn change(&mut self, _props: Self::Properties) -> ShouldRender {
@@ -35,14 +60,21 @@ impl Component for Index {
     }
 
     fn view(&self) -> Html {
+        let locale = self.locale;
+
         html! {
-            <Layout>
+            <Layout locale=locale on_locale=self.link.callback(IndexMsg::SetLocale)>
                 <Router<AppRoute, ()>
-                    render = Router::render(|switch: AppRoute| {
+                    render = Router::render(move |switch: AppRoute| {
                         match switch {
-                            AppRoute::Decrypt(id) => html!{<ReceiveForm id = id/>},
-                            AppRoute::Encrypt => html!{<SendForm />},
-                            AppRoute::Upload => html!{<Upload />},
+                            // The access secret in the fragment is read separately
+                            AppRoute::Decrypt(id) | AppRoute::DecryptLocalized(_, id) => {
+                                let id = id.split('#').next().unwrap_or_default().to_owned();
+                                html!{<ReceiveForm id = id locale = locale/>}
+                            },
+                            AppRoute::Encrypt => html!{<SendForm locale = locale/>},
+                            AppRoute::Upload => html!{<Upload locale = locale/>},
+                            AppRoute::Sent => html!{<SentMessages locale = locale/>},
                         }
                     })
                 />
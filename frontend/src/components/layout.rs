@@ -1,11 +1,17 @@
-use yew::prelude::{html, Children, Component, ComponentLink, Properties, ShouldRender};
+use common::Locale;
+use yew::prelude::{
+    html, Callback, Children, Component, ComponentLink, MouseEvent, Properties, ShouldRender,
+};
 
 use crate::components::common::title::Title;
+use crate::i18n::{Text, Translate};
 
 #[derive(Clone, PartialEq, Properties)]
 pub struct Props {
     #[prop_or_default]
     pub children: Children,
+    pub locale: Locale,
+    pub on_locale: Callback<Locale>,
 }
 
 pub struct Layout {
@@ -24,6 +30,20 @@ impl Component for Layout {
     fn view(&self) -> yew::Html {
         html! {
             <main>
+                <div class="select language" role="group" aria-label=self.props.locale.t(Text::Language)>
+                    { for Locale::ALL.iter().map(|&locale| {
+                        let on_locale = self.props.on_locale.clone();
+                        html!{
+                            <button
+                                type="button"
+                                class=if locale == self.props.locale { "selected" } else { "" }
+                                onclick=Callback::from(move |_: MouseEvent| on_locale.emit(locale))
+                            >
+                                {locale.code().to_uppercase()}
+                            </button>
+                        }
+                    })}
+                </div>
                 <Title>{"TGuard"}</Title>
                 { for self.props.children.iter() }
             </main>
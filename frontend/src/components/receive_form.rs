@@ -1,9 +1,11 @@
+use common::Locale;
 use wasm_bindgen_futures::spawn_local;
-use yew::prelude::{html, Component, ComponentLink, Html, Properties, ShouldRender};
+use yew::prelude::{html, Component, ComponentLink, Html, MouseEvent, Properties, ShouldRender};
 
+use crate::i18n::{Text, Translate};
 use crate::types::{File, ReceivedData};
 use crate::{
-    actions::download_and_decrypt,
+    actions::{download_and_decrypt, sign_receipt},
     components::common::{
         alert::{Alert, AlertKind},
         loader::Loader,
@@ -13,12 +15,25 @@ use crate::{
 #[derive(Properties, Clone, PartialEq, Debug)]
 pub struct Props {
     pub id: String,
+    pub locale: Locale,
 }
 
 pub enum ReceiveFormMsg {
     Initial,
     Error,
+    Withdrawn,
+    Exhausted,
     Update(ReceivedData),
+    SignReceipt,
+    ReceiptSigned(bool),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ReceiptStatus {
+    Unsigned,
+    Signing,
+    Signed,
+    Failed,
 }
 
 #[derive(Debug)]
@@ -27,6 +42,9 @@ pub struct ReceiveForm {
     link: ComponentLink<Self>,
     data: ReceivedData,
     error: bool,
+    withdrawn: bool,
+    exhausted: bool,
+    receipt: ReceiptStatus,
 }
 
 impl Component for ReceiveForm {
@@ -40,6 +58,9 @@ impl Component for ReceiveForm {
             link,
             data: Default::default(),
             error: false,
+            withdrawn: false,
+            exhausted: false,
+            receipt: ReceiptStatus::Unsigned,
         }
     }
 
@@ -58,37 +79,88 @@ impl Component for ReceiveForm {
             Self::Message::Error => {
                 self.error = true;
             }
+            Self::Message::Withdrawn => {
+                self.withdrawn = true;
+            }
+            Self::Message::Exhausted => {
+                self.exhausted = true;
+            }
             Self::Message::Update(data) => {
                 self.data = data;
             }
+            Self::Message::SignReceipt => {
+                let link = self.link.clone();
+                let id = self.props.id.clone();
+                let hash = self.data.hash.clone();
+                let locale = self.props.locale;
+                self.receipt = ReceiptStatus::Signing;
+
+                spawn_local(async move {
+                    let signed = sign_receipt(&id, &hash, locale).await.is_some();
+                    link.send_message(Self::Message::ReceiptSigned(signed));
+                });
+            }
+            Self::Message::ReceiptSigned(signed) => {
+                self.receipt = if signed {
+                    ReceiptStatus::Signed
+                } else {
+                    ReceiptStatus::Failed
+                };
+            }
         };
 
         true
     }
 
-    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
-        false
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        // Only the language can change, the message is downloaded once
+        if self.props.locale != props.locale {
+            self.props.locale = props.locale;
+            true
+        } else {
+            false
+        }
     }
 
     fn view(&self) -> Html {
+        let locale = self.props.locale;
+
+        if self.withdrawn {
+            return html! {
+                <Alert kind=AlertKind::Error>
+                    {locale.t(Text::WithdrawnBySender)}
+                </Alert>
+            };
+        }
+        if self.exhausted {
+            return html! {
+                <Alert kind=AlertKind::Error>
+                    {locale.t(Text::DownloadLimitReached)}
+                </Alert>
+            };
+        }
+
         html! {
           <>
             {
                 if self.error {
                     html!{
                         <Alert kind=AlertKind::Error>
-                            {"An error occured, please "}
-                            <a href="." target="_blank">{"try again."}</a>
+                            {locale.t(Text::ErrorTryAgain)}
+                            <a href="." target="_blank">{locale.t(Text::TryAgain)}</a>
                         </Alert>
                     }
                 } else if !self.data.message.is_empty() {
                     html!{
-                        <Alert kind=AlertKind::Success>{"Message decrypted successfully."}</Alert>
+                        <>
+                            <Alert kind=AlertKind::Success>{locale.t(Text::DecryptedSuccessfully)}</Alert>
+                            { self.view_receipt() }
+                        </>
                     }
                 } else {
                     html!{
                         <Alert kind=AlertKind::Empty>
-                            {"Decrypt a secret message using "}
+                            {locale.t(Text::DecryptIntro)}
                             <a href="https://irma.app" target="_blank">{"IRMA"}</a>
                         </Alert>
                     }
@@ -103,19 +175,19 @@ impl Component for ReceiveForm {
                     html!{
                         <>
                             <dl>
-                                <dt>{"Sender:"}</dt>
+                                <dt>{locale.t(Text::Sender)}</dt>
                                 <dd>{&self.data.from}</dd>
-                                <dt>{"To:"}</dt>
+                                <dt>{locale.t(Text::To)}</dt>
                                 <dd>{&self.data.to}</dd>
-                                <dt>{"Subject:"}</dt>
+                                <dt>{locale.t(Text::Subject)}</dt>
                                 <dd>{&self.data.subject}</dd>
                                 { if self.data.signed {
                                     html!{
-                                      <dt>{"Signed Message:"}</dt>
+                                      <dt>{locale.t(Text::SignedMessage)}</dt>
                                     }
                                 } else {
                                     html!{
-                                        <dt>{"Message:"}</dt>
+                                        <dt>{locale.t(Text::Message)}</dt>
                                     }
                                 }}
                                 <dd>
@@ -126,13 +198,13 @@ impl Component for ReceiveForm {
                             </dl>
                             { if !self.data.attachments.is_empty() {
                                 html!{
-                                    <label>{"Attachments:"}</label>
+                                    <label>{locale.t(Text::Attachments)}</label>
                                 }
                             } else {
                                 html!{}
                             }}
                             <table class="files">
-                                { for self.data.attachments.iter().map(Self::view_file) }
+                                { for self.data.attachments.iter().map(|file| Self::view_file(locale, file)) }
                             </table>
                         </>
                     }
@@ -144,7 +216,44 @@ impl Component for ReceiveForm {
 }
 
 impl ReceiveForm {
-    fn view_file(data: &File) -> Html {
+    fn view_receipt(&self) -> Html {
+        let locale = self.props.locale;
+
+        // Messages with a download limit may be deleted once decrypted, so no
+        // receipt can be stored for them
+        if self.data.download_limit.is_some() {
+            return html! {
+                <p>{locale.t(Text::LimitedDownloads)}</p>
+            };
+        }
+
+        match self.receipt {
+            ReceiptStatus::Signed => html! {
+                <p>{locale.t(Text::ReceiptConfirmed)}</p>
+            },
+            ReceiptStatus::Signing => html! {
+                <p>{locale.t(Text::SignReceiptInApp)}</p>
+            },
+            ReceiptStatus::Unsigned | ReceiptStatus::Failed => html! {
+                <p>
+                    { if self.receipt == ReceiptStatus::Failed {
+                        html!{ {locale.t(Text::ReceiptFailed)} }
+                    } else {
+                        html!{ {locale.t(Text::ReceiptRequest)} }
+                    }}
+                    <button
+                        type="button"
+                        class="outlined"
+                        onclick=self.link.callback(|e: MouseEvent| { e.prevent_default(); ReceiveFormMsg::SignReceipt })
+                    >
+                        {locale.t(Text::SignReceipt)}
+                    </button>
+                </p>
+            },
+        }
+    }
+
+    fn view_file(locale: Locale, data: &File) -> Html {
         let content = base64::encode(&data.content);
 
         html! {
@@ -161,7 +270,7 @@ impl ReceiveForm {
                         href={format!("data:{};base64,{}", data.mimetype, content)}
                         target="_blank"
                     >
-                            {"download"}
+                            {locale.t(Text::Download)}
                     </a>
                 </td>
             </tr>
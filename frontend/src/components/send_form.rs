@@ -1,26 +1,32 @@
 pub mod attribute_header_row;
 pub mod attribute_input;
 pub mod recipient_row;
+pub mod share_link;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use wasm_bindgen_futures::spawn_local;
 use yew::prelude::{
     html, ChangeData, Component, ComponentLink, FocusEvent, Html, InputData, MouseEvent,
-    ShouldRender,
+    Properties, ShouldRender,
 };
 use yew::services::reader::{File, FileData, ReaderService, ReaderTask};
 
-use common::{AttributeIdentifier, AttributeValue};
+use common::{AttributeIdentifier, AttributeValue, Channel, DownloadLimit, Locale, SendResult};
 
 use crate::actions::{encrypt_and_submit, sign, SendError};
 use crate::attributes::{
-    attribute_label, attribute_type, chosen_attribute_options, AttributeType,
+    address_attribute, attribute_label, attribute_type, chosen_attribute_options, AttributeType,
     EMAIL_ATTRIBUTE_IDENTIFIER,
 };
 use crate::components::{
     common::alert::{Alert, AlertKind},
-    send_form::{attribute_header_row::AttributeHeaderRow, recipient_row::RecipientRow},
+    send_form::{
+        attribute_header_row::AttributeHeaderRow, recipient_row::RecipientRow,
+        share_link::ShareLink,
+    },
 };
+use crate::i18n::{Text, Translate};
+use crate::js_functions::withdraw_message;
 use crate::mime::convert_to_mime;
 use crate::types::{FormData, Recipient};
 
@@ -31,7 +37,7 @@ pub enum SendFormStatus {
     Initial,
     Error(SendError),
     Encrypting,
-    Sent,
+    Sent(SendResult),
 }
 
 impl SendFormStatus {
@@ -39,7 +45,7 @@ impl SendFormStatus {
         match self {
             Self::Initial => "initial",
             Self::Encrypting => "encrypting",
-            Self::Sent => "sent",
+            Self::Sent(_) => "sent",
             Self::Error(_) => "error",
         }
         .into()
@@ -52,6 +58,7 @@ pub enum SendFormMsg {
     UpdateStatus(SendFormStatus),
     AddTo,
     UpdateTo(usize, String),
+    UpdateChannel(usize, Channel),
     DeleteTo(usize),
     AddAttr(AttributeIdentifier),
     DeleteAttr(usize),
@@ -62,27 +69,45 @@ pub enum SendFormMsg {
     AddFiles(Vec<File>),
     LoadedFile((FileName, FileData)),
     DeleteFile(usize),
+    UpdateDownloadLimit(Option<DownloadLimit>),
+    UpdateLocale(Option<Locale>),
+    ToggleLinkOnly,
+    ToggleShareLink,
+    ToggleSenderCopy,
+    Withdraw(String, String),
+    Withdrawn(String, bool),
+}
+
+#[derive(Properties, Clone, PartialEq)]
+pub struct Props {
+    pub locale: Locale,
 }
 
 pub struct SendForm {
     link: ComponentLink<Self>,
+    props: Props,
     status: SendFormStatus,
     form: FormData,
     attributes: Vec<AttributeIdentifier>,
     tasks: HashMap<FileName, ReaderTask>,
+    withdrawn: HashSet<String>,
+    withdraw_failed: bool,
 }
 
 impl Component for SendForm {
-    type Properties = ();
+    type Properties = Props;
     type Message = SendFormMsg;
 
-    fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
         Self {
             link,
+            props,
             status: SendFormStatus::Initial,
             form: Default::default(),
             attributes: vec![AttributeIdentifier(EMAIL_ATTRIBUTE_IDENTIFIER.to_owned()); 1],
             tasks: HashMap::default(),
+            withdrawn: HashSet::default(),
+            withdraw_failed: false,
         }
     }
 
@@ -93,23 +118,34 @@ impl Component for SendForm {
                 attributes.iter_mut().for_each(|attr| {
                     attr.value = String::new();
                 });
+                // New recipients are notified by email
+                if let Some(address_index) = self.address_index() {
+                    attributes[address_index].identifier =
+                        AttributeIdentifier(EMAIL_ATTRIBUTE_IDENTIFIER.to_owned());
+                }
 
                 self.form.to.push(Recipient {
                     to: String::new(),
+                    channel: Channel::Email,
                     attributes,
                 });
             }
             Self::Message::UpdateTo(index, to) => {
                 self.form.to[index].to = to.clone();
 
-                if let Some(email_index) = self.form.to[index]
-                    .attributes
-                    .iter()
-                    .position(|attr| attr.identifier.0 == EMAIL_ATTRIBUTE_IDENTIFIER)
-                {
-                    self.form.to[index].attributes[email_index].value = to;
+                if let Some(address_index) = self.address_index() {
+                    self.form.to[index].attributes[address_index].value = to;
                 }
             }
+            Self::Message::UpdateChannel(index, channel) => {
+                // The message is sealed for the phone number of recipients
+                // notified by SMS, instead of their email address
+                if let Some(address_index) = self.address_index() {
+                    self.form.to[index].attributes[address_index].identifier =
+                        AttributeIdentifier(address_attribute(&channel).to_owned());
+                }
+                self.form.to[index].channel = channel;
+            }
             Self::Message::DeleteTo(index) => {
                 if self.form.to.len() > 1 {
                     self.form.to.remove(index);
@@ -119,7 +155,11 @@ impl Component for SendForm {
                 self.attributes.push(identifier.clone());
                 self.form.to.iter_mut().for_each(|el| {
                     el.attributes.push(AttributeValue {
-                        identifier: identifier.clone(),
+                        identifier: if identifier.0 == EMAIL_ATTRIBUTE_IDENTIFIER {
+                            AttributeIdentifier(address_attribute(&el.channel).to_owned())
+                        } else {
+                            identifier.clone()
+                        },
                         value: if identifier.0 == EMAIL_ATTRIBUTE_IDENTIFIER {
                             el.to.clone()
                         } else if attribute_type(&identifier) == AttributeType::Boolean {
@@ -150,6 +190,8 @@ impl Component for SendForm {
                     self.attributes =
                         vec![AttributeIdentifier(EMAIL_ATTRIBUTE_IDENTIFIER.to_owned()); 1];
                     self.tasks = HashMap::default();
+                    self.withdrawn = HashSet::default();
+                    self.withdraw_failed = false;
                 }
 
                 self.status = status;
@@ -160,7 +202,7 @@ impl Component for SendForm {
 
                 spawn_local(async move {
                     let message = convert_to_mime(&form);
-                    match sign(&link, &message).await {
+                    match sign(&link, &message, form.locale.unwrap_or_default()).await {
                         Err(e) => {
                             link.send_message(Self::Message::UpdateStatus(SendFormStatus::Error(
                                 e,
@@ -209,19 +251,43 @@ impl Component for SendForm {
             Self::Message::DeleteFile(index) => {
                 self.form.attachments.remove(index);
             }
+            Self::Message::UpdateDownloadLimit(limit) => self.form.download_limit = limit,
+            Self::Message::UpdateLocale(locale) => self.form.locale = locale,
+            Self::Message::ToggleLinkOnly => self.form.link_only = !self.form.link_only,
+            Self::Message::ToggleShareLink => self.form.share_link = !self.form.share_link,
+            Self::Message::ToggleSenderCopy => self.form.sender_copy = !self.form.sender_copy,
+            Self::Message::Withdraw(id, management_token) => {
+                let link = self.link.clone();
+
+                spawn_local(async move {
+                    let withdrawn = withdraw_message(&id, &management_token).await.is_some();
+                    link.send_message(Self::Message::Withdrawn(id, withdrawn));
+                });
+            }
+            Self::Message::Withdrawn(id, withdrawn) => {
+                self.withdraw_failed = !withdrawn;
+                if withdrawn {
+                    self.withdrawn.insert(id);
+                }
+            }
         };
 
         true
     }
 
-    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
-        false
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        if self.props != props {
+            self.props = props;
+            true
+        } else {
+            false
+        }
     }
 
     fn view(&self) -> Html {
         let disabled = match self.status {
             SendFormStatus::Initial | SendFormStatus::Error(_) => false,
-            SendFormStatus::Encrypting | SendFormStatus::Sent => true,
+            SendFormStatus::Encrypting | SendFormStatus::Sent(_) => true,
         };
         let multiple = self.form.to.len() > 1;
         let chosen = chosen_attribute_options(&self.attributes);
@@ -229,6 +295,8 @@ impl Component for SendForm {
             .attributes
             .iter()
             .find(|&attr| attr == &AttributeIdentifier(EMAIL_ATTRIBUTE_IDENTIFIER.to_owned()));
+        let locale = self.props.locale;
+        let address_index = self.address_index();
 
         html! {
             <form
@@ -238,14 +306,79 @@ impl Component for SendForm {
                 {
                     match &self.status {
                         SendFormStatus::Error(e) => html!{
-                            <Alert kind=AlertKind::Error>{format!("Error: {}", e)}</Alert>
+                            <Alert kind=AlertKind::Error>{format!("{}: {}", locale.t(Text::Error), e)}</Alert>
                         },
-                        SendFormStatus::Sent => html!{
-                            <Alert kind=AlertKind::Success>{"Message encrypted and sent successfully."}</Alert>
+                        SendFormStatus::Sent(result) => html!{
+                            <>
+                                <Alert kind=AlertKind::Success>{locale.t(Text::SentSuccessfully)}</Alert>
+                                <p>{locale.t(Text::KeepManagementToken)}</p>
+                                <p>
+                                    {locale.t(Text::SeeSentMessages)}
+                                    <a href="/sent">{locale.t(Text::SentMessagesLink)}</a>
+                                    {"."}
+                                </p>
+                                { if self.withdraw_failed {
+                                    html!{
+                                        <Alert kind=AlertKind::Error>{locale.t(Text::WithdrawFailed)}</Alert>
+                                    }
+                                } else {
+                                    html!{}
+                                }}
+                                <table class="sent">
+                                    <tr>
+                                        <th>{locale.t(Text::Recipient)}</th>
+                                        <th>{locale.t(Text::MessageColumn)}</th>
+                                        <th>{locale.t(Text::ManagementToken)}</th>
+                                        <th></th>
+                                    </tr>
+                                    { for result.accepted.iter().map(|sent| {
+                                        let id = sent.id.clone();
+                                        let management_token = sent.management_token.clone();
+
+                                        html!{
+                                            <>
+                                            <tr>
+                                                <td>
+                                                    {sent.to.clone()}
+                                                    { if sent.sender_copy { locale.t(Text::YourCopy) } else { "" } }
+                                                </td>
+                                                <td><a href={format!("/download/{}#{}", sent.id, sent.access_secret)}>{sent.id.clone()}</a></td>
+                                                <td><code>{sent.management_token.clone()}</code></td>
+                                                <td>
+                                                    { if self.withdrawn.contains(&sent.id) {
+                                                        html!{ {locale.t(Text::Withdrawn)} }
+                                                    } else {
+                                                        html!{
+                                                            <button
+                                                                type="button"
+                                                                class="outlined delete"
+                                                                onclick=self.link.callback(move |_| Self::Message::Withdraw(id.clone(), management_token.clone()))
+                                                            >
+                                                                {locale.t(Text::Withdraw)}
+                                                            </button>
+                                                        }
+                                                    }}
+                                                </td>
+                                            </tr>
+                                            { match &sent.url {
+                                                Some(url) => html!{
+                                                    <tr>
+                                                        <td colspan="4">
+                                                            <ShareLink url=url.clone() locale=locale/>
+                                                        </td>
+                                                    </tr>
+                                                },
+                                                None => html!{},
+                                            }}
+                                            </>
+                                        }
+                                    })}
+                                </table>
+                            </>
                         },
                         _ => html!{
                             <Alert kind=AlertKind::Empty>
-                                {"Send encrypted messages with "}
+                                {locale.t(Text::SendIntro)}
                                 <a href="https://irma.app" target="_blank" rel="noopener noreferrer">{"IRMA"}</a>
                             </Alert>
                         }
@@ -253,7 +386,7 @@ impl Component for SendForm {
                 }
                 <div>
                     <div>
-                        <label>{"Your email:"}</label>
+                        <label>{locale.t(Text::YourEmail)}</label>
                         <input
                             type="email"
                             name="from"
@@ -271,6 +404,7 @@ impl Component for SendForm {
                                 count=self.form.to.len()
                                 add_attribute=self.link.callback(Self::Message::AddAttr)
                                 delete_attribute=self.link.callback(Self::Message::DeleteAttr)
+                                locale=locale
                             />
                         </tr>
                         { for self.form.to.iter().enumerate().map(|(index, to): (usize, &Recipient)| {
@@ -280,7 +414,10 @@ impl Component for SendForm {
                                     to=to.clone()
                                     disabled=disabled
                                     multiple=multiple
+                                    locale=locale
+                                    address_index=address_index
                                     update_to=self.link.callback(move |value: String| Self::Message::UpdateTo(index, value))
+                                    update_channel=self.link.callback(move |channel: Channel| Self::Message::UpdateChannel(index, channel))
                                     update_attribute_value=self.link.callback(move |(attr_index, value): (usize, String)| Self::Message::UpdateAttrValue(index, attr_index, value))
                                     delete_to=self.link.callback(move |_| Self::Message::DeleteTo(index))
                                 />
@@ -294,18 +431,18 @@ impl Component for SendForm {
                             onclick=self.link.callback(|e: MouseEvent| { e.prevent_default(); Self::Message::AddTo })
                         >
                             {"+ "}
-                            {"Add recipient"}
+                            {locale.t(Text::AddRecipient)}
                         </button>
 
                         { if chosen.len() != self.attributes.len() + if email_chosen.is_some() { 0 } else { 1 } {
                             html!{
                                 <div>
                                     <label class="light">
-                                        {"Add attribute for encryption:"}
+                                        {locale.t(Text::AddAttribute)}
                                     </label>
                                     { for chosen.iter().map(|(chosen_index, attr)| {
                                         let attr_clone = attr.clone();
-                                        let label = attribute_label(attr);
+                                        let label = attribute_label(attr, locale);
 
                                         if attr.0 == EMAIL_ATTRIBUTE_IDENTIFIER {
                                             return html!{};
@@ -336,10 +473,10 @@ impl Component for SendForm {
                             html!{
                                 <div class="delete">
                                     <label class="light">
-                                        {"Delete attribute for encryption:"}
+                                        {locale.t(Text::DeleteAttribute)}
                                     </label>
                                     { for chosen.into_iter().map(|(chosen_index, attr)| {
-                                        let label = attribute_label(&attr);
+                                        let label = attribute_label(&attr, locale);
 
                                         if attr.0 == EMAIL_ATTRIBUTE_IDENTIFIER {
                                             return html!{};
@@ -370,7 +507,7 @@ impl Component for SendForm {
                         }}
                     </div>
                     <div>
-                        <label>{"Subject:"}</label>
+                        <label>{locale.t(Text::Subject)}</label>
                         <input
                             name="subject"
                             maxlength="256"
@@ -381,7 +518,7 @@ impl Component for SendForm {
                         />
                     </div>
                     <div>
-                        <label>{"Message:"}</label>
+                        <label>{locale.t(Text::Message)}</label>
                         <textarea
                             name="content"
                             maxlength="16384"
@@ -392,7 +529,7 @@ impl Component for SendForm {
                         />
                     </div>
                     <div>
-                        <label>{"Attachments:"}</label>
+                        <label>{locale.t(Text::Attachments)}</label>
                         <input
                             type="file"
                             multiple=true
@@ -440,15 +577,124 @@ impl Component for SendForm {
                             html!{}
                         }}
                     </div>
+                    <div>
+                        <label>{locale.t(Text::Downloads)}</label>
+                        <select
+                            name="download_limit"
+                            disabled={disabled}
+                            onchange=self.link.callback(|value| {
+                                let limit = match value {
+                                    ChangeData::Select(select) => match select.value().as_str() {
+                                        "count" => Some(DownloadLimit::Count(1)),
+                                        "decrypted" => Some(DownloadLimit::UntilDecrypted),
+                                        _ => None,
+                                    },
+                                    _ => None,
+                                };
+                                Self::Message::UpdateDownloadLimit(limit)
+                            })
+                        >
+                            <option value="unlimited" selected={self.form.download_limit.is_none()}>
+                                {locale.t(Text::Unlimited)}
+                            </option>
+                            <option value="count" selected={matches!(self.form.download_limit, Some(DownloadLimit::Count(_)))}>
+                                {locale.t(Text::LimitedTimes)}
+                            </option>
+                            <option value="decrypted" selected={self.form.download_limit == Some(DownloadLimit::UntilDecrypted)}>
+                                {locale.t(Text::UntilDecrypted)}
+                            </option>
+                        </select>
+                        { if let Some(DownloadLimit::Count(count)) = self.form.download_limit {
+                            html!{
+                                <input
+                                    type="number"
+                                    name="download_count"
+                                    min="1"
+                                    max="100"
+                                    disabled={disabled}
+                                    value=count.to_string()
+                                    oninput=self.link.callback(|event: InputData| {
+                                        let count = event.value.parse::<u32>().unwrap_or(1).max(1);
+                                        Self::Message::UpdateDownloadLimit(Some(DownloadLimit::Count(count)))
+                                    })
+                                />
+                            }
+                        } else {
+                            html!{}
+                        }}
+                    </div>
+                    <div>
+                        <label>{locale.t(Text::EmailLanguage)}</label>
+                        <select
+                            name="locale"
+                            disabled={disabled}
+                            onchange=self.link.callback(|value| {
+                                let locale = match value {
+                                    ChangeData::Select(select) => Locale::from_code(&select.value()),
+                                    _ => None,
+                                };
+                                Self::Message::UpdateLocale(locale)
+                            })
+                        >
+                            <option value="auto" selected={self.form.locale.is_none()}>
+                                {locale.t(Text::EmailLanguageAuto)}
+                            </option>
+                            <option value="nl" selected={self.form.locale == Some(Locale::Nl)}>
+                                {"Nederlands"}
+                            </option>
+                            <option value="en" selected={self.form.locale == Some(Locale::En)}>
+                                {"English"}
+                            </option>
+                        </select>
+                    </div>
+                    <div>
+                        <label>
+                            <input
+                                type="checkbox"
+                                name="link_only"
+                                disabled={disabled}
+                                checked=self.form.link_only
+                                onclick=self.link.callback(|_| Self::Message::ToggleLinkOnly)
+                            />
+                            {" "}
+                            {locale.t(Text::LinkOnly)}
+                        </label>
+                    </div>
+                    <div>
+                        <label>
+                            <input
+                                type="checkbox"
+                                name="share_link"
+                                disabled={disabled}
+                                checked=self.form.share_link
+                                onclick=self.link.callback(|_| Self::Message::ToggleShareLink)
+                            />
+                            {" "}
+                            {locale.t(Text::ShareLink)}
+                        </label>
+                    </div>
+                    <div>
+                        <label>
+                            <input
+                                type="checkbox"
+                                name="sender_copy"
+                                disabled={disabled}
+                                checked=self.form.sender_copy
+                                onclick=self.link.callback(|_| Self::Message::ToggleSenderCopy)
+                            />
+                            {" "}
+                            {locale.t(Text::SenderCopy)}
+                        </label>
+                    </div>
                     <div>
                     {
-                        if self.status == SendFormStatus::Sent {
+                        if matches!(self.status, SendFormStatus::Sent(_)) {
                             html!{
                                 <button
                                     type="button"
                                     onclick=self.link.callback(|e: MouseEvent| { e.prevent_default(); Self::Message::UpdateStatus(SendFormStatus::Initial) })
                                 >
-                                    {"Reset"}
+                                    {locale.t(Text::Reset)}
                                 </button>
                             }
                         } else {
@@ -461,9 +707,9 @@ impl Component for SendForm {
                                         <svg role="img" xmlns="http://www.w3.org/2000/svg" viewBox="0 0 512 512">
                                             <path fill="currentColor" d="M464 4.3L16 262.7C-7 276-4.7 309.9 19.8 320L160 378v102c0 30.2 37.8 43.3 56.7 20.3l60.7-73.8 126.4 52.2c19.1 7.9 40.7-4.2 43.8-24.7l64-417.1C515.7 10.2 487-9 464 4.3zM192 480v-88.8l54.5 22.5L192 480zm224-30.9l-206.2-85.2 199.5-235.8c4.8-5.6-2.9-13.2-8.5-8.4L145.5 337.3 32 290.5 480 32l-64 417.1z"></path>
                                         </svg>
-                                        {"Send"}
+                                        {locale.t(Text::Send)}
                                     </button>
-                                    {" or "}
+                                    {locale.t(Text::Or)}
                                     <button
                                         type="submit"
                                         disabled={disabled}
@@ -472,7 +718,7 @@ impl Component for SendForm {
                                         <svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 640 512">
                                             <path fill="currentColor" d="M630.1 206.8c-32.7 8.4-77.1 30.8-108.7 49.6-14.7 8.8-29.9 17.9-41.5 23.4-31.3 15.2-58.3 28.2-84.1 28.2-12.9 0-23-4.3-29.9-12.7-11.3-13.8-11.3-35.3-7.9-63.3 3.3-26.9.6-47.8-7.6-57.3-4.4-5-10-6.9-18.7-6.3C307 170 257 210.8 169.6 300.9l-54.4 56 70-187.6c11.8-31.6 3.6-66.1-20.9-87.8C145 64.3 112 54.3 77.3 78L3.5 127.8c-3.5 2.3-4.5 7-2.5 10.6l9.2 16.2c2.3 4.1 7.6 5.3 11.5 2.7L97.5 106c6.6-4.5 14-6.8 21.4-6.8 9.1 0 17.6 3.4 24.8 9.8 13.2 11.7 17.6 30.2 11.3 47.1L55.2 423.7c-1.9 5.2-1 12.6 2.2 17.7 2.4 3.7 6.6 6.1 11.3 6.6 4.9.3 9.7-1.4 13-4.9C125 396.8 239.5 278.4 298 228.4l20.4-17.4c3.4-2.9 8.5-.3 8.2 4.1l-2.1 27.9c-2 27.3-2.4 55.9 16.8 78.6 12.4 14.5 30.7 21.9 54.6 21.9 32.7 0 64.1-15.1 97.3-31.1 10.2-4.9 24.9-14.1 39.2-23 30.9-19.3 72.3-40.5 101.8-47.7 3.5-.9 5.9-4 5.9-7.6v-17.3c-.1-7.4-5-11.2-10-10z"></path>
                                         </svg>
-                                        {"Sign and send"}
+                                        {locale.t(Text::SignAndSend)}
                                     </button>
                                 </div>
                             }
@@ -484,3 +730,13 @@ impl Component for SendForm {
         }
     }
 }
+
+impl SendForm {
+    /// Column of the attribute sealed for the address of each recipient, if
+    /// the message is sealed for it.
+    fn address_index(&self) -> Option<usize> {
+        self.attributes
+            .iter()
+            .position(|attr| attr.0 == EMAIL_ATTRIBUTE_IDENTIFIER)
+    }
+}
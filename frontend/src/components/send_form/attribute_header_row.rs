@@ -1,5 +1,6 @@
 use crate::attributes::{attribute_label, EMAIL_ATTRIBUTE_IDENTIFIER};
-use common::AttributeIdentifier;
+use crate::i18n::{Text, Translate};
+use common::{AttributeIdentifier, Locale};
 use yew::prelude::*;
 
 #[derive(Clone, Debug, PartialEq, Properties)]
@@ -8,6 +9,7 @@ pub struct Props {
     pub attributes: Vec<AttributeIdentifier>,
     pub add_attribute: Callback<AttributeIdentifier>,
     pub delete_attribute: Callback<usize>,
+    pub locale: Locale,
 }
 
 pub enum AttributeHeaderRowMsg {
@@ -74,12 +76,16 @@ impl Component for AttributeHeaderRow {
             .iter()
             .enumerate()
             .filter(|&(_, attr)| attr.0 != EMAIL_ATTRIBUTE_IDENTIFIER);
+        let locale = self.props.locale;
 
         html! {
             <>
                 <th>
                     <div>
-                        <label>{ if self.props.count > 1 { "Recipients:" } else { "Recipient:" }}</label>
+                        <label>
+                            { if self.props.count > 1 { locale.t(Text::Recipients) } else { locale.t(Text::Recipient) }}
+                            {":"}
+                        </label>
                         {if render_attributes.clone().count() > 0  {
                             html! {
                                 <label class="checkbox-label">
@@ -89,7 +95,7 @@ impl Component for AttributeHeaderRow {
                                         checked={email_index != None}
                                         onclick=self.link.callback(move |_| Self::Message::ToggleEmail(email_index))
                                     />
-                                    {"Encrypt for e-mail address"}
+                                    {locale.t(Text::EncryptForEmail)}
                                 </label>
                             }
                         } else {
@@ -102,7 +108,7 @@ impl Component for AttributeHeaderRow {
                         <th>
                             <div>
                                 <label>
-                                    {attribute_label(attribute)}
+                                    {attribute_label(attribute, locale)}
                                 </label>
                                 <button
                                     class="delete small"
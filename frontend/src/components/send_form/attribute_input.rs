@@ -1,4 +1,4 @@
-use common::AttributeValue;
+use common::{AttributeValue, Locale};
 use yew::prelude::*;
 
 use crate::attributes::{attribute_label, attribute_type, AttributeType};
@@ -7,6 +7,7 @@ use crate::attributes::{attribute_label, attribute_type, AttributeType};
 pub struct Props {
     pub attribute: AttributeValue,
     pub disabled: bool,
+    pub locale: Locale,
     pub update_attribute_value: Callback<String>,
 }
 
@@ -47,7 +48,8 @@ impl Component for AttributeInput {
     }
 
     fn view(&self) -> Html {
-        let label = attribute_label(&self.props.attribute.identifier).to_string();
+        let label =
+            attribute_label(&self.props.attribute.identifier, self.props.locale).to_string();
         match attribute_type(&self.props.attribute.identifier) {
             AttributeType::Boolean => html! {
                 <p>{self.props.attribute.value.clone()}</p>
@@ -1,22 +1,28 @@
+use common::{Channel, Locale};
 use yew::prelude::*;
 
-use crate::attributes::EMAIL_ATTRIBUTE_IDENTIFIER;
 use crate::components::send_form::attribute_input::AttributeInput;
+use crate::i18n::{Text, Translate};
 use crate::types::Recipient;
 
 #[derive(Clone, Debug, PartialEq, Properties)]
 pub struct Props {
     pub update_to: Callback<String>,
+    pub update_channel: Callback<Channel>,
     pub update_attribute_value: Callback<(usize, String)>,
     pub delete_to: Callback<()>,
     pub index: usize,
     pub disabled: bool,
     pub multiple: bool,
     pub to: Recipient,
+    /// Index of the attribute sealed for the address, shown in the first column
+    pub address_index: Option<usize>,
+    pub locale: Locale,
 }
 
 pub enum AttributeRowMsg {
     UpdateTo(String),
+    UpdateChannel(Channel),
     UpdateAttrValue(usize, String),
     DeleteTo,
 }
@@ -39,6 +45,9 @@ impl Component for RecipientRow {
             AttributeRowMsg::UpdateTo(value) => {
                 self.props.update_to.emit(value);
             }
+            AttributeRowMsg::UpdateChannel(channel) => {
+                self.props.update_channel.emit(channel);
+            }
             AttributeRowMsg::UpdateAttrValue(attr_index, value) => {
                 self.props.update_attribute_value.emit((attr_index, value));
             }
@@ -60,27 +69,50 @@ impl Component for RecipientRow {
     }
 
     fn view(&self) -> Html {
+        let locale = self.props.locale;
+        let sms = self.props.to.channel == Channel::Sms;
+        let address_index = self.props.address_index;
+
         html! {
             <tr>
                 <td>
-                    <input
-                        type="email"
-                        name="to"
-                        maxlength="512"
-                        required=true
-                        placeholder="user@example.com"
-                        disabled={self.props.disabled}
-                        value=self.props.to.to.clone()
-                        class="inline"
-                        oninput=self.link.callback(move |event: InputData| Self::Message::UpdateTo(event.value))
-                    />
+                    <div class="channel">
+                        <select
+                            name="channel"
+                            title=locale.t(Text::NotifyBy)
+                            disabled={self.props.disabled}
+                            onchange=self.link.callback(|value| {
+                                let channel = match value {
+                                    ChangeData::Select(select) => Channel::from_code(&select.value()).unwrap_or_default(),
+                                    _ => Channel::Email,
+                                };
+                                Self::Message::UpdateChannel(channel)
+                            })
+                        >
+                            <option value="email" selected={!sms}>{locale.t(Text::ChannelEmail)}</option>
+                            <option value="sms" selected={sms}>{locale.t(Text::ChannelSms)}</option>
+                        </select>
+                        <input
+                            type={if sms { "tel" } else { "email" }}
+                            name="to"
+                            maxlength="512"
+                            required=true
+                            pattern={if sms { "\\+[0-9]{8,15}" } else { ".*" }}
+                            placeholder={if sms { "+31612345678" } else { "user@example.com" }}
+                            disabled={self.props.disabled}
+                            value=self.props.to.to.clone()
+                            class="inline"
+                            oninput=self.link.callback(move |event: InputData| Self::Message::UpdateTo(event.value))
+                        />
+                    </div>
                 </td>
-                { for self.props.to.attributes.iter().enumerate().filter(|(_, attr)| attr.identifier.0 != EMAIL_ATTRIBUTE_IDENTIFIER).map(|(attr_index, attr)| {
+                { for self.props.to.attributes.iter().enumerate().filter(|(attr_index, _)| Some(*attr_index) != address_index).map(|(attr_index, attr)| {
                     html!{
                         <td>
                             <AttributeInput
                                 attribute=attr.clone()
                                 disabled=self.props.disabled
+                                locale=self.props.locale
                                 update_attribute_value=self.link.callback(move |value: String| Self::Message::UpdateAttrValue(attr_index, value))
                             />
                         </td>
@@ -0,0 +1,107 @@
+use common::Locale;
+use qrcode::render::svg;
+use qrcode::QrCode;
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+
+use crate::i18n::{Text, Translate};
+use crate::js_functions::copy_text;
+
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub struct Props {
+    pub url: String,
+    pub locale: Locale,
+}
+
+pub enum ShareLinkMsg {
+    Copy,
+    Copied(bool),
+}
+
+/// Download link to hand over to the recipient, with a button to copy it and
+/// a QR code to scan it.
+pub struct ShareLink {
+    props: Props,
+    link: ComponentLink<Self>,
+    copied: bool,
+}
+
+/// QR code of the link as an SVG data url, to show in an image.
+fn qr_code(url: &str) -> Option<String> {
+    let image = QrCode::new(url.as_bytes())
+        .ok()?
+        .render::<svg::Color>()
+        .min_dimensions(200, 200)
+        .build();
+    Some(format!("data:image/svg+xml;base64,{}", base64::encode(image)))
+}
+
+impl Component for ShareLink {
+    type Properties = Props;
+    type Message = ShareLinkMsg;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        ShareLink {
+            props,
+            link,
+            copied: false,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            ShareLinkMsg::Copy => {
+                let link = self.link.clone();
+                let url = self.props.url.clone();
+
+                spawn_local(async move {
+                    let copied = copy_text(url).await.as_bool().unwrap_or(false);
+                    link.send_message(ShareLinkMsg::Copied(copied));
+                });
+                false
+            }
+            ShareLinkMsg::Copied(copied) => {
+                self.copied = copied;
+                true
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        if self.props != props {
+            self.props = props;
+            self.copied = false;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn view(&self) -> Html {
+        let locale = self.props.locale;
+
+        html! {
+            <div class="share-link">
+                <div>
+                    <input
+                        type="text"
+                        name="share_link"
+                        readonly=true
+                        value=self.props.url.clone()
+                    />
+                    <button
+                        type="button"
+                        class="outlined"
+                        onclick=self.link.callback(|_| ShareLinkMsg::Copy)
+                    >
+                        { if self.copied { locale.t(Text::Copied) } else { locale.t(Text::CopyLink) } }
+                    </button>
+                </div>
+                { match qr_code(&self.props.url) {
+                    Some(src) => html!{ <img src=src alt=locale.t(Text::QrCode) /> },
+                    None => html!{},
+                }}
+            </div>
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::{html, Component, ComponentLink, Html, Properties, ShouldRender};
+
+use common::{Locale, MessageEvent, MessageStatus, Receipt};
+
+use crate::components::common::alert::{Alert, AlertKind};
+use crate::i18n::{Text, Translate};
+use crate::js_functions::{message_receipt, message_status};
+use crate::sent::{load_sent, SentRecord};
+
+pub enum SentMessagesMsg {
+    Status(String, Option<MessageStatus>),
+    Receipt(String, Receipt),
+}
+
+fn event_label(event: &MessageEvent) -> Text {
+    match event.event.as_str() {
+        "notification_sent" => Text::Notified,
+        "notification_bounced" => Text::NotificationFailed,
+        "page_fetched" => Text::Opened,
+        "content_fetched" => Text::DownloadedEvent,
+        "receipt_signed" => Text::ReceiptSigned,
+        _ => Text::UnknownEvent,
+    }
+}
+
+fn format_time(time: u64, locale: Locale) -> String {
+    js_sys::Date::new(&((time * 1000) as f64).into())
+        .to_locale_string(locale.code(), &Default::default())
+        .into()
+}
+
+#[derive(Properties, Clone, PartialEq)]
+pub struct Props {
+    pub locale: Locale,
+}
+
+/// Overview of the messages sent from this browser, with what happened to them.
+pub struct SentMessages {
+    props: Props,
+    records: Vec<SentRecord>,
+    statuses: HashMap<String, Option<MessageStatus>>,
+    receipts: HashMap<String, Receipt>,
+}
+
+impl Component for SentMessages {
+    type Properties = Props;
+    type Message = SentMessagesMsg;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let records = load_sent();
+        for record in &records {
+            let link = link.clone();
+            let id = record.id.clone();
+            let management_token = record.management_token.clone();
+
+            spawn_local(async move {
+                let status = message_status(&id, &management_token).await;
+                let signed = status.as_ref().map_or(false, |status| {
+                    status.events.iter().any(|e| e.event == "receipt_signed")
+                });
+                link.send_message(Self::Message::Status(id.clone(), status));
+
+                if signed {
+                    if let Some(receipt) = message_receipt(&id, &management_token).await {
+                        link.send_message(Self::Message::Receipt(id, receipt));
+                    }
+                }
+            });
+        }
+
+        Self {
+            props,
+            records,
+            statuses: HashMap::default(),
+            receipts: HashMap::default(),
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Self::Message::Status(id, status) => {
+                self.statuses.insert(id, status);
+            }
+            Self::Message::Receipt(id, receipt) => {
+                self.receipts.insert(id, receipt);
+            }
+        }
+
+        true
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        if self.props != props {
+            self.props = props;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn view(&self) -> Html {
+        let locale = self.props.locale;
+
+        if self.records.is_empty() {
+            return html! {
+                <Alert kind=AlertKind::Empty>
+                    {locale.t(Text::NoMessagesSent)}
+                    <a href="/">{locale.t(Text::SendAMessage)}</a>
+                </Alert>
+            };
+        }
+
+        html! {
+            <table class="sent">
+                <tr>
+                    <th>{locale.t(Text::Recipient)}</th>
+                    <th>{locale.t(Text::SubjectColumn)}</th>
+                    <th>{locale.t(Text::Status)}</th>
+                    <th></th>
+                </tr>
+                { for self.records.iter().map(|record| html!{
+                    <tr>
+                        <td>{record.to.clone()}</td>
+                        <td>{record.subject.clone()}</td>
+                        <td>
+                            { match self.statuses.get(&record.id) {
+                                None => html!{ {locale.t(Text::Loading)} },
+                                Some(None) => html!{ {locale.t(Text::Unavailable)} },
+                                Some(Some(status)) if status.events.is_empty() => html!{ {locale.t(Text::Pending)} },
+                                Some(Some(status)) => html!{
+                                    <ul>
+                                        { for status.events.iter().map(|event| html!{
+                                            <li>{format!("{}: {}", locale.t(event_label(event)), format_time(event.time, locale))}</li>
+                                        })}
+                                    </ul>
+                                },
+                            }}
+                        </td>
+                        <td>{ self.view_receipt(&record.id) }</td>
+                    </tr>
+                })}
+            </table>
+        }
+    }
+}
+
+impl SentMessages {
+    fn view_receipt(&self, id: &str) -> Html {
+        match self
+            .receipts
+            .get(id)
+            .and_then(|receipt| serde_json::to_vec(receipt).ok())
+        {
+            Some(receipt) => html! {
+                <a
+                    class="button outlined"
+                    download={format!("receipt-{}.json", id)}
+                    href={format!("data:application/json;base64,{}", base64::encode(&receipt))}
+                >
+                    {self.props.locale.t(Text::DownloadReceipt)}
+                </a>
+            },
+            None => html! {},
+        }
+    }
+}
@@ -1,14 +1,16 @@
+use common::Locale;
 use std::collections::HashMap;
 use wasm_bindgen_futures::spawn_local;
 use yew::{
     html,
     services::reader::{File, FileData, ReaderService, ReaderTask},
-    ChangeData, Component, ComponentLink, Html, ShouldRender,
+    ChangeData, Component, ComponentLink, Html, Properties, ShouldRender,
 };
 
 use crate::{
     components::common::alert::{Alert, AlertKind},
     decrypt::{decrypt_file, DecryptError},
+    i18n::{Text, Translate},
     mime::convert_from_mime,
     types::File as FileType,
 };
@@ -31,8 +33,14 @@ pub enum UploadMsg {
     AddFiles(Vec<File>),
 }
 
+#[derive(Properties, Clone, PartialEq)]
+pub struct Props {
+    pub locale: Locale,
+}
+
 pub struct Upload {
     link: ComponentLink<Self>,
+    props: Props,
     status: UploadFormStatus,
     decrypted: Vec<(FileName, Message, Vec<FileType>)>,
     tasks: HashMap<FileName, ReaderTask>,
@@ -45,12 +53,13 @@ fn convert_and_parse(raw: &[u8]) -> Option<(String, Vec<FileType>)> {
 }
 
 impl Component for Upload {
-    type Properties = ();
+    type Properties = Props;
     type Message = UploadMsg;
 
-    fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
         Self {
             link,
+            props,
             status: UploadFormStatus::Initial,
             tasks: HashMap::default(),
             decrypted: vec![],
@@ -113,24 +122,31 @@ impl Component for Upload {
         }
     }
 
-    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
-        false
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        if self.props != props {
+            self.props = props;
+            true
+        } else {
+            false
+        }
     }
 
     fn view(&self) -> Html {
+        let locale = self.props.locale;
+
         html! {
             <>
                 {
                     match &self.status {
                         UploadFormStatus::Error(e) => html!{
-                            <Alert kind=AlertKind::Error>{format!("Error: {}, please try a different file", e)}</Alert>
+                            <Alert kind=AlertKind::Error>{format!("{}: {}, {}", locale.t(Text::Error), e, locale.t(Text::TryDifferentFile))}</Alert>
                         },
                         UploadFormStatus::Success => html!{
-                            <Alert kind=AlertKind::Success>{"Message decrypted successfully"}</Alert>
+                            <Alert kind=AlertKind::Success>{locale.t(Text::DecryptedSuccessfully)}</Alert>
                         },
                         _ => html!{
                             <Alert kind=AlertKind::Empty>
-                                {"Select an IRMASeal file to decrypt"}
+                                {locale.t(Text::SelectFile)}
                             </Alert>
                         }
                     }
@@ -151,7 +167,7 @@ impl Component for Upload {
                 </div>
                 { if !self.decrypted.is_empty() {
                     html!{
-                        { for self.decrypted.iter().map(Self::view_decrypted) }
+                        { for self.decrypted.iter().map(|decrypted| Self::view_decrypted(locale, decrypted)) }
                     }
                 } else {
                     html!{}
@@ -162,18 +178,18 @@ impl Component for Upload {
 }
 
 impl Upload {
-    fn view_decrypted(decrypted: &(FileName, Message, Vec<FileType>)) -> Html {
+    fn view_decrypted(locale: Locale, decrypted: &(FileName, Message, Vec<FileType>)) -> Html {
         html! {
             <div class="decrypted">
                 <dl>
-                    <dt>{"File name:"}</dt>
+                    <dt>{locale.t(Text::FileName)}</dt>
                     <dd>{decrypted.0.clone()}</dd>
                     {if decrypted.1.is_empty() {
                         html!{}
                     } else {
                         html!{
                             <>
-                                <dt>{"Message:"}</dt>
+                                <dt>{locale.t(Text::Message)}</dt>
                                 <dd>
                                     <pre>
                                       {decrypted.1.clone()}
@@ -185,19 +201,19 @@ impl Upload {
                 </dl>
                 <label>
                     {if decrypted.1.is_empty() {
-                        "Attachments:"
+                        locale.t(Text::Attachments)
                     } else {
-                        "Decrypted:"
+                        locale.t(Text::Decrypted)
                     }}
                 </label>
                 <table class="files">
-                    { for decrypted.2.iter().map(Self::view_file) }
+                    { for decrypted.2.iter().map(|file| Self::view_file(locale, file)) }
                 </table>
             </div>
         }
     }
 
-    fn view_file(data: &FileType) -> Html {
+    fn view_file(locale: Locale, data: &FileType) -> Html {
         let content = base64::encode(&data.content);
 
         html! {
@@ -214,7 +230,7 @@ impl Upload {
                         href={format!("data:{};base64,{}", data.mimetype, content)}
                         target="_blank"
                     >
-                            {"download"}
+                            {locale.t(Text::Download)}
                     </a>
                 </td>
             </tr>
@@ -7,7 +7,8 @@ use wasm_bindgen::JsValue;
 use yew::services::reader::FileData;
 
 use crate::actions::decrypt_message;
-use crate::js_functions::IrmaSession;
+use crate::i18n::{self, Text, Translate};
+use crate::js_functions::{IrmaSession, PKG_URL};
 
 #[derive(Debug, PartialEq)]
 pub enum DecryptError {
@@ -18,11 +19,12 @@ pub enum DecryptError {
 
 impl Display for DecryptError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            DecryptError::Deserialize => write!(f, "failed to deserialize"),
-            DecryptError::Failed => write!(f, "failed to decrypt"),
-            DecryptError::Unknown => write!(f, "unknown file type"),
-        }
+        let text = match self {
+            DecryptError::Deserialize => Text::DeserializeFailed,
+            DecryptError::Failed => Text::DecryptFailed,
+            DecryptError::Unknown => Text::UnknownFileType,
+        };
+        write!(f, "{}", i18n::current().t(text))
     }
 }
 
@@ -73,6 +75,7 @@ async fn decrypt_irmaseal_file(sealed: &IrmaSealedMessage) -> Result<Vec<u8>, De
             .unwrap()
             .to_string(),
         timestamp: sealed.metadata.identity.timestamp,
+        pkg_url: PKG_URL.to_owned(),
     };
 
     let session = match JsValue::from_serde(&irma_session) {
@@ -0,0 +1,182 @@
+mod en;
+mod nl;
+
+use std::cell::Cell;
+
+use common::Locale;
+use yew::format::{Json, Text as Stored};
+use yew::services::storage::{Area, StorageService};
+
+const STORAGE_KEY: &str = "tguard.locale";
+
+/// Texts shown in the user interface, each language has a catalog translating
+/// all of them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Text {
+    Language,
+    Error,
+    // Send form
+    SendIntro,
+    SentSuccessfully,
+    KeepManagementToken,
+    SeeSentMessages,
+    SentMessagesLink,
+    WithdrawFailed,
+    Recipient,
+    Recipients,
+    MessageColumn,
+    ManagementToken,
+    Withdrawn,
+    Withdraw,
+    YourEmail,
+    EncryptForEmail,
+    NotifyBy,
+    ChannelEmail,
+    ChannelSms,
+    AddRecipient,
+    AddAttribute,
+    DeleteAttribute,
+    Subject,
+    Message,
+    Attachments,
+    Downloads,
+    Unlimited,
+    LimitedTimes,
+    UntilDecrypted,
+    EmailLanguage,
+    EmailLanguageAuto,
+    LinkOnly,
+    ShareLink,
+    SenderCopy,
+    YourCopy,
+    CopyLink,
+    Copied,
+    QrCode,
+    Reset,
+    Send,
+    Or,
+    SignAndSend,
+    // Receive form
+    WithdrawnBySender,
+    DownloadLimitReached,
+    ErrorTryAgain,
+    TryAgain,
+    DecryptedSuccessfully,
+    DecryptIntro,
+    Sender,
+    To,
+    SignedMessage,
+    LimitedDownloads,
+    ReceiptConfirmed,
+    SignReceiptInApp,
+    ReceiptFailed,
+    ReceiptRequest,
+    SignReceipt,
+    Download,
+    // Upload
+    TryDifferentFile,
+    SelectFile,
+    FileName,
+    Decrypted,
+    // Sent messages
+    NoMessagesSent,
+    SendAMessage,
+    SubjectColumn,
+    Status,
+    Loading,
+    Unavailable,
+    Pending,
+    Notified,
+    NotificationFailed,
+    Opened,
+    DownloadedEvent,
+    ReceiptSigned,
+    UnknownEvent,
+    DownloadReceipt,
+    // Attributes
+    Email,
+    FullName,
+    Iban,
+    PhoneNumber,
+    // Errors
+    PublicKeyNotFound,
+    SignFailed,
+    EncryptFailed,
+    SerializeFailed,
+    NotSent,
+    TooLarge,
+    RateLimited,
+    DeserializeFailed,
+    DecryptFailed,
+    UnknownFileType,
+}
+
+/// Translation of texts into a language.
+pub trait Translate {
+    fn t(self, text: Text) -> &'static str;
+}
+
+impl Translate for Locale {
+    fn t(self, text: Text) -> &'static str {
+        match self {
+            Locale::Nl => nl::text(text),
+            Locale::En => en::text(text),
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT: Cell<Locale> = Cell::new(Locale::default());
+}
+
+/// Language the interface is shown in, for texts outside of components such
+/// as error messages.
+pub fn current() -> Locale {
+    CURRENT.with(|current| current.get())
+}
+
+pub fn set_current(locale: Locale) {
+    CURRENT.with(|current| current.set(locale));
+}
+
+/// Remember the language chosen with the switcher for later visits.
+pub fn remember(locale: Locale) {
+    if let Ok(mut storage) = StorageService::new(Area::Local) {
+        storage.store(STORAGE_KEY, Json(&locale));
+    }
+}
+
+/// Language to start in: the one in the path of a download link, the one
+/// chosen earlier, or the language of the browser.
+pub fn detect() -> Locale {
+    let window = match web_sys::window() {
+        Some(window) => window,
+        None => return Locale::default(),
+    };
+
+    let from_path = window.location().pathname().ok().and_then(|path| {
+        let mut segments = path.trim_start_matches('/').split('/');
+        match (segments.next(), segments.next()) {
+            (Some(code), Some("download")) => Locale::from_code(code),
+            _ => None,
+        }
+    });
+
+    let remembered = || {
+        let stored: Stored = StorageService::new(Area::Local).ok()?.restore(STORAGE_KEY);
+        serde_json::from_str(&stored.ok()?).ok()
+    };
+
+    let browser = || {
+        let language = window.navigator().language()?;
+        Some(match Locale::from_code(language.split('-').next()?) {
+            Some(locale) => locale,
+            None => Locale::En,
+        })
+    };
+
+    from_path
+        .or_else(remembered)
+        .or_else(browser)
+        .unwrap_or_default()
+}
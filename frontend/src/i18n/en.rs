@@ -0,0 +1,106 @@
+use super::Text;
+
+pub fn text(text: Text) -> &'static str {
+    match text {
+        Text::Language => "Language",
+        Text::Error => "Error",
+        Text::SendIntro => "Send encrypted messages with ",
+        Text::SentSuccessfully => "Message encrypted and sent successfully.",
+        Text::KeepManagementToken => {
+            "Keep the management token of a message to manage it later, it is only shown once."
+        }
+        Text::SeeSentMessages => "See whether your messages were read under ",
+        Text::SentMessagesLink => "sent messages",
+        Text::WithdrawFailed => "The message could not be withdrawn.",
+        Text::Recipient => "Recipient",
+        Text::Recipients => "Recipients",
+        Text::MessageColumn => "Message",
+        Text::ManagementToken => "Management token",
+        Text::Withdrawn => "Withdrawn",
+        Text::Withdraw => "Withdraw",
+        Text::YourEmail => "Your email:",
+        Text::EncryptForEmail => "Encrypt for e-mail address",
+        Text::NotifyBy => "Notify by",
+        Text::ChannelEmail => "Email",
+        Text::ChannelSms => "SMS",
+        Text::AddRecipient => "Add recipient",
+        Text::AddAttribute => "Add attribute for encryption:",
+        Text::DeleteAttribute => "Delete attribute for encryption:",
+        Text::Subject => "Subject:",
+        Text::Message => "Message:",
+        Text::Attachments => "Attachments:",
+        Text::Downloads => "Downloads:",
+        Text::Unlimited => "Unlimited",
+        Text::LimitedTimes => "Limited number of times",
+        Text::UntilDecrypted => "Until decrypted",
+        Text::EmailLanguage => "Email language:",
+        Text::EmailLanguageAuto => "Based on the recipient's address",
+        Text::LinkOnly => "Only email the download link, without the encrypted message attached",
+        Text::ShareLink => {
+            "Do not email the recipients, I will hand over the download link myself"
+        }
+        Text::SenderCopy => "Send me a copy, which I can open with my own email address",
+        Text::YourCopy => " (your copy)",
+        Text::CopyLink => "Copy link",
+        Text::Copied => "Copied",
+        Text::QrCode => "QR code of the download link",
+        Text::Reset => "Reset",
+        Text::Send => "Send",
+        Text::Or => " or ",
+        Text::SignAndSend => "Sign and send",
+        Text::WithdrawnBySender => "This message was withdrawn by the sender.",
+        Text::DownloadLimitReached => {
+            "This message can no longer be downloaded, it was downloaded as often as the sender allowed."
+        }
+        Text::ErrorTryAgain => "An error occured, please ",
+        Text::TryAgain => "try again.",
+        Text::DecryptedSuccessfully => "Message decrypted successfully.",
+        Text::DecryptIntro => "Decrypt a secret message using ",
+        Text::Sender => "Sender:",
+        Text::To => "To:",
+        Text::SignedMessage => "Signed Message:",
+        Text::LimitedDownloads => {
+            "This message can only be downloaded a limited number of times, save it if you need it later."
+        }
+        Text::ReceiptConfirmed => {
+            "You confirmed receiving this message, the sender can see your signed receipt."
+        }
+        Text::SignReceiptInApp => "Sign the receipt using the IRMA app.",
+        Text::ReceiptFailed => "The receipt could not be signed. ",
+        Text::ReceiptRequest => "The sender may ask you to confirm receiving this message. ",
+        Text::SignReceipt => "Sign receipt",
+        Text::Download => "download",
+        Text::TryDifferentFile => "please try a different file",
+        Text::SelectFile => "Select an IRMASeal file to decrypt",
+        Text::FileName => "File name:",
+        Text::Decrypted => "Decrypted:",
+        Text::NoMessagesSent => "No messages were sent from this browser. ",
+        Text::SendAMessage => "Send a message",
+        Text::SubjectColumn => "Subject",
+        Text::Status => "Status",
+        Text::Loading => "Loading...",
+        Text::Unavailable => "Unavailable",
+        Text::Pending => "Pending",
+        Text::Notified => "Notified",
+        Text::NotificationFailed => "Notification failed",
+        Text::Opened => "Opened",
+        Text::DownloadedEvent => "Downloaded",
+        Text::ReceiptSigned => "Receipt signed",
+        Text::UnknownEvent => "Unknown",
+        Text::DownloadReceipt => "Download receipt",
+        Text::Email => "Email",
+        Text::FullName => "Full name",
+        Text::Iban => "IBAN",
+        Text::PhoneNumber => "Phone number",
+        Text::PublicKeyNotFound => "public key not found",
+        Text::SignFailed => "sign failed",
+        Text::EncryptFailed => "failed to encrypt the message",
+        Text::SerializeFailed => "failed to serialize the message",
+        Text::NotSent => "message could not be sent",
+        Text::TooLarge => "the message is too large, please limit the total size to 1 MB",
+        Text::RateLimited => "too many messages were sent, please try again later",
+        Text::DeserializeFailed => "failed to deserialize",
+        Text::DecryptFailed => "failed to decrypt",
+        Text::UnknownFileType => "unknown file type",
+    }
+}
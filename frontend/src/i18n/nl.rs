@@ -0,0 +1,108 @@
+use super::Text;
+
+pub fn text(text: Text) -> &'static str {
+    match text {
+        Text::Language => "Taal",
+        Text::Error => "Fout",
+        Text::SendIntro => "Verstuur versleutelde berichten met ",
+        Text::SentSuccessfully => "Bericht versleuteld en verstuurd.",
+        Text::KeepManagementToken => {
+            "Bewaar de beheercode van een bericht om het later te beheren, deze wordt maar één keer getoond."
+        }
+        Text::SeeSentMessages => "Bekijk of uw berichten gelezen zijn onder ",
+        Text::SentMessagesLink => "verstuurde berichten",
+        Text::WithdrawFailed => "Het bericht kon niet worden ingetrokken.",
+        Text::Recipient => "Ontvanger",
+        Text::Recipients => "Ontvangers",
+        Text::MessageColumn => "Bericht",
+        Text::ManagementToken => "Beheercode",
+        Text::Withdrawn => "Ingetrokken",
+        Text::Withdraw => "Intrekken",
+        Text::YourEmail => "Uw e-mailadres:",
+        Text::EncryptForEmail => "Versleutel voor e-mailadres",
+        Text::NotifyBy => "Melden via",
+        Text::ChannelEmail => "E-mail",
+        Text::ChannelSms => "Sms",
+        Text::AddRecipient => "Ontvanger toevoegen",
+        Text::AddAttribute => "Attribuut voor versleuteling toevoegen:",
+        Text::DeleteAttribute => "Attribuut voor versleuteling verwijderen:",
+        Text::Subject => "Onderwerp:",
+        Text::Message => "Bericht:",
+        Text::Attachments => "Bijlagen:",
+        Text::Downloads => "Downloads:",
+        Text::Unlimited => "Onbeperkt",
+        Text::LimitedTimes => "Beperkt aantal keer",
+        Text::UntilDecrypted => "Tot het ontsleuteld is",
+        Text::EmailLanguage => "Taal van de e-mail:",
+        Text::EmailLanguageAuto => "Op basis van het adres van de ontvanger",
+        Text::LinkOnly => {
+            "Alleen de downloadlink mailen, zonder het versleutelde bericht als bijlage"
+        }
+        Text::ShareLink => "De ontvangers niet mailen, ik geef de downloadlink zelf door",
+        Text::SenderCopy => "Stuur mij een kopie, die ik met mijn eigen e-mailadres kan openen",
+        Text::YourCopy => " (uw kopie)",
+        Text::CopyLink => "Link kopiëren",
+        Text::Copied => "Gekopieerd",
+        Text::QrCode => "QR-code van de downloadlink",
+        Text::Reset => "Opnieuw",
+        Text::Send => "Versturen",
+        Text::Or => " of ",
+        Text::SignAndSend => "Ondertekenen en versturen",
+        Text::WithdrawnBySender => "Dit bericht is door de afzender ingetrokken.",
+        Text::DownloadLimitReached => {
+            "Dit bericht kan niet meer worden gedownload, het is zo vaak gedownload als de afzender toestond."
+        }
+        Text::ErrorTryAgain => "Er is een fout opgetreden, ",
+        Text::TryAgain => "probeer het opnieuw.",
+        Text::DecryptedSuccessfully => "Bericht ontsleuteld.",
+        Text::DecryptIntro => "Ontsleutel een geheim bericht met ",
+        Text::Sender => "Afzender:",
+        Text::To => "Aan:",
+        Text::SignedMessage => "Ondertekend bericht:",
+        Text::LimitedDownloads => {
+            "Dit bericht kan maar een beperkt aantal keer gedownload worden, bewaar het als u het later nog nodig heeft."
+        }
+        Text::ReceiptConfirmed => {
+            "U heeft de ontvangst van dit bericht bevestigd, de afzender kan uw ondertekende ontvangstbevestiging inzien."
+        }
+        Text::SignReceiptInApp => "Onderteken de ontvangstbevestiging met de IRMA-app.",
+        Text::ReceiptFailed => "De ontvangstbevestiging kon niet worden ondertekend. ",
+        Text::ReceiptRequest => {
+            "De afzender kan u vragen de ontvangst van dit bericht te bevestigen. "
+        }
+        Text::SignReceipt => "Ontvangst bevestigen",
+        Text::Download => "downloaden",
+        Text::TryDifferentFile => "probeer een ander bestand",
+        Text::SelectFile => "Kies een IRMASeal-bestand om te ontsleutelen",
+        Text::FileName => "Bestandsnaam:",
+        Text::Decrypted => "Ontsleuteld:",
+        Text::NoMessagesSent => "Er zijn geen berichten verstuurd vanuit deze browser. ",
+        Text::SendAMessage => "Verstuur een bericht",
+        Text::SubjectColumn => "Onderwerp",
+        Text::Status => "Status",
+        Text::Loading => "Laden...",
+        Text::Unavailable => "Niet beschikbaar",
+        Text::Pending => "In behandeling",
+        Text::Notified => "Gemeld",
+        Text::NotificationFailed => "Melding mislukt",
+        Text::Opened => "Geopend",
+        Text::DownloadedEvent => "Gedownload",
+        Text::ReceiptSigned => "Ontvangst bevestigd",
+        Text::UnknownEvent => "Onbekend",
+        Text::DownloadReceipt => "Ontvangstbevestiging downloaden",
+        Text::Email => "E-mailadres",
+        Text::FullName => "Volledige naam",
+        Text::Iban => "IBAN",
+        Text::PhoneNumber => "Telefoonnummer",
+        Text::PublicKeyNotFound => "publieke sleutel niet gevonden",
+        Text::SignFailed => "ondertekenen mislukt",
+        Text::EncryptFailed => "het bericht kon niet worden versleuteld",
+        Text::SerializeFailed => "het bericht kon niet worden omgezet",
+        Text::NotSent => "het bericht kon niet worden verstuurd",
+        Text::TooLarge => "het bericht is te groot, beperk de totale grootte tot 1 MB",
+        Text::RateLimited => "er zijn te veel berichten verstuurd, probeer het later opnieuw",
+        Text::DeserializeFailed => "het bestand kon niet worden gelezen",
+        Text::DecryptFailed => "ontsleutelen mislukt",
+        Text::UnknownFileType => "onbekend bestandstype",
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use common::DownloadResult;
+use common::{DownloadResult, MessageStatus, Receipt, SendResult, EXHAUSTED, TOO_LARGE};
 use js_sys::Uint8Array;
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::{
@@ -12,33 +12,177 @@ use web_sys::{Request, RequestInit, Response};
 
 use crate::actions::SendError;
 
+// Base url of the PKG, can be pointed to a development PKG at build time
+pub const PKG_URL: &str = match option_env!("PKG_URL") {
+    Some(url) => url,
+    None => "https://irmacrypt.nl/pkg",
+};
+
 #[derive(Deserialize, Serialize)]
 pub struct IrmaSession {
     pub attribute_identifier: String,
     pub attribute_value: String,
     pub timestamp: u64,
+    pub pkg_url: String,
 }
 
 #[wasm_bindgen(module = "/script/js_functions.js")]
 extern "C" {
-    pub async fn encrypt(message: String, key: &[u8], iv: &[u8]) -> JsValue;
-    pub async fn decrypt(ciphertext: &[u8], key: &[u8], iv: &[u8]) -> JsValue;
+    pub async fn copy_text(text: String) -> JsValue;
     pub async fn decrypt_cfb_hmac(ciphertext: &[u8], key: &[u8], iv: &[u8]) -> JsValue;
     pub async fn irma_get_usk(session: JsValue) -> JsValue;
-    pub async fn irma_sign(hash: String) -> JsValue;
+    pub async fn irma_sign(hash: String, locale: String) -> JsValue;
+    pub async fn irma_sign_receipt(id: String, hash: String, time: u64, locale: String) -> JsValue;
 }
 
-pub async fn download_bytes(url: &str) -> Option<Vec<u8>> {
+async fn fetch(url: &str) -> Option<Response> {
     let window = web_sys::window()?;
     let response = JsFuture::from(window.fetch_with_str(url)).await.ok()?;
-    let response: Response = response.dyn_into().ok()?;
+    response.dyn_into().ok()
+}
+
+async fn response_bytes(response: &Response) -> Option<Vec<u8>> {
     let data = JsFuture::from(response.array_buffer().ok()?).await.ok()?;
     Some(Uint8Array::new(&data).to_vec())
 }
 
-pub async fn download(id: &str) -> Option<DownloadResult> {
-    let data = download_bytes(&format!("/api/download/{}", id)).await?;
-    Some(serde_json::from_slice(&data).ok()?)
+pub async fn download_bytes(url: &str) -> Option<Vec<u8>> {
+    response_bytes(&fetch(url).await?).await
+}
+
+pub enum Download {
+    Found(DownloadResult),
+    Withdrawn,
+    Exhausted,
+}
+
+// Access secret of the message being downloaded, from the fragment of the link
+pub fn access_secret() -> Option<String> {
+    let hash = web_sys::window()?.location().hash().ok()?;
+    let secret = hash.trim_start_matches('#');
+    if secret.is_empty() {
+        None
+    } else {
+        Some(secret.to_owned())
+    }
+}
+
+pub async fn download(id: &str, access_secret: Option<&str>) -> Option<Download> {
+    let request = Request::new_with_str(&format!("/api/download/{}", id)).ok()?;
+    if let Some(secret) = access_secret {
+        request.headers().set("X-Access-Secret", secret).ok()?;
+    }
+
+    let window = web_sys::window()?;
+    let response: Response = JsFuture::from(window.fetch_with_request(&request))
+        .await
+        .ok()?
+        .dyn_into()
+        .ok()?;
+    let data = response_bytes(&response).await?;
+    // Both withdrawn messages and those whose download limit ran out are gone
+    if response.status() == 410 {
+        return Some(if data == EXHAUSTED.as_bytes() {
+            Download::Exhausted
+        } else {
+            Download::Withdrawn
+        });
+    }
+
+    Some(Download::Found(serde_json::from_slice(&data).ok()?))
+}
+
+// Report a message as decrypted, deleting it if it may only be downloaded until then
+pub async fn confirm_decrypted(id: &str, access_secret: Option<&str>) -> Option<()> {
+    let mut opts = RequestInit::new();
+    opts.method("POST");
+    let request =
+        Request::new_with_str_and_init(&format!("/api/download/{}/decrypted", id), &opts).ok()?;
+    if let Some(secret) = access_secret {
+        request.headers().set("X-Access-Secret", secret).ok()?;
+    }
+
+    let window = web_sys::window()?;
+    let response: Response = JsFuture::from(window.fetch_with_request(&request))
+        .await
+        .ok()?
+        .dyn_into()
+        .ok()?;
+    if response.ok() {
+        Some(())
+    } else {
+        None
+    }
+}
+
+// Withdraw a sent message using its management token
+pub async fn withdraw_message(id: &str, management_token: &str) -> Option<()> {
+    let mut opts = RequestInit::new();
+    opts.method("DELETE");
+    let request = Request::new_with_str_and_init(&format!("/api/message/{}", id), &opts).ok()?;
+
+    request
+        .headers()
+        .set("X-Management-Token", management_token)
+        .ok()?;
+
+    let window = web_sys::window()?;
+    let response: Response = JsFuture::from(window.fetch_with_request(&request))
+        .await
+        .ok()?
+        .dyn_into()
+        .ok()?;
+    if response.ok() {
+        Some(())
+    } else {
+        None
+    }
+}
+
+// Events recorded for a sent message
+pub async fn message_status(id: &str, management_token: &str) -> Option<MessageStatus> {
+    let request = Request::new_with_str(&format!("/api/message/{}/status", id)).ok()?;
+
+    request
+        .headers()
+        .set("X-Management-Token", management_token)
+        .ok()?;
+
+    let window = web_sys::window()?;
+    let response: Response = JsFuture::from(window.fetch_with_request(&request))
+        .await
+        .ok()?
+        .dyn_into()
+        .ok()?;
+    if !response.ok() {
+        return None;
+    }
+
+    let data = response_bytes(&response).await?;
+    serde_json::from_slice(&data).ok()
+}
+
+// Receipt signed by the recipient of a sent message
+pub async fn message_receipt(id: &str, management_token: &str) -> Option<Receipt> {
+    let request = Request::new_with_str(&format!("/api/message/{}/receipt", id)).ok()?;
+
+    request
+        .headers()
+        .set("X-Management-Token", management_token)
+        .ok()?;
+
+    let window = web_sys::window()?;
+    let response: Response = JsFuture::from(window.fetch_with_request(&request))
+        .await
+        .ok()?
+        .dyn_into()
+        .ok()?;
+    if !response.ok() {
+        return None;
+    }
+
+    let data = response_bytes(&response).await?;
+    serde_json::from_slice(&data).ok()
 }
 
 #[derive(Deserialize)]
@@ -47,7 +191,7 @@ struct PublicKeyResponse {
 }
 
 pub async fn get_public_key() -> Option<String> {
-    let data = download_bytes("https://irmacrypt.nl/pkg/v1/parameters").await?;
+    let data = download_bytes(&format!("{}/v1/parameters", PKG_URL)).await?;
     Some(
         serde_json::from_slice::<PublicKeyResponse>(&data)
             .ok()?
@@ -55,7 +199,7 @@ pub async fn get_public_key() -> Option<String> {
     )
 }
 
-pub async fn send_message(body: &str) -> Result<(), SendError> {
+pub async fn send_message(body: &str) -> Result<SendResult, SendError> {
     let mut opts = RequestInit::new();
     opts.method("POST");
     opts.body(Some(&body.into()));
@@ -74,10 +218,15 @@ pub async fn send_message(body: &str) -> Result<(), SendError> {
         .dyn_into()
         .map_err(|_| SendError::NotSent)?;
 
+    let data = response_bytes(&response).await.ok_or(SendError::NotSent)?;
     if response.status() >= 200 && response.status() < 300 {
-        Ok(())
-    } else if response.status() == 413 {
+        serde_json::from_slice(&data).map_err(|_| SendError::NotSent)
+    } else if response.status() == 413
+        || (response.status() == 400 && data == TOO_LARGE.as_bytes())
+    {
         Err(SendError::TooLarge)
+    } else if response.status() == 429 {
+        Err(SendError::RateLimited)
     } else {
         Err(SendError::NotSent)
     }
@@ -2,7 +2,9 @@ pub mod actions;
 pub mod attributes;
 pub mod components;
 pub mod decrypt;
+pub mod i18n;
 pub mod ibs;
 pub mod js_functions;
 pub mod mime;
+pub mod sent;
 pub mod types;
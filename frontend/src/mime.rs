@@ -1,40 +1,25 @@
-use lettre::message::{header::ContentType, Attachment, MultiPart, SinglePart};
+use common::mime::Attachment;
 use mail_parser::{BodyPart, Message, MessagePart, MimeHeaders};
-use rand::{distributions::Alphanumeric, thread_rng, Rng};
-use std::iter::repeat_with;
+use rand::thread_rng;
 
 use crate::types::{File, FormData};
 
-// Based on: https://docs.rs/lettre/0.10.0-rc.4/src/lettre/message/mimebody.rs.html#174 , but without fastrand
-fn make_boundary() -> String {
-    let mut rng = thread_rng();
-    repeat_with(|| rng.sample(Alphanumeric)).take(40).collect()
-}
-
 pub fn convert_to_mime(form: &FormData) -> String {
-    let text = SinglePart::plain(form.message.clone());
-
-    // manually set the ContenType and boundary because fastrand gives problems
-    let boundary = make_boundary();
-    let mut content = MultiPart::builder()
-        .header(
-            ContentType::parse(format!("multipart/mixed; boundary={}", boundary).as_ref()).unwrap(),
-        )
-        .singlepart(text);
-
-    for attachment in &form.attachments {
-        // try to guess the mime type from the file name
-        let mime_type = mime_guess::from_path(&attachment.name)
-            .first_raw()
-            .unwrap_or("application/octet-stream");
-        let part = Attachment::new(attachment.name.clone()).body(
-            attachment.content.clone(),
-            ContentType::parse(mime_type).unwrap(),
-        );
-        content = content.singlepart(part.clone());
-    }
+    let attachments: Vec<Attachment> = form
+        .attachments
+        .iter()
+        .map(|attachment| Attachment {
+            name: attachment.name.clone(),
+            // try to guess the mime type from the file name
+            content_type: mime_guess::from_path(&attachment.name)
+                .first_raw()
+                .unwrap_or("application/octet-stream")
+                .to_owned(),
+            content: attachment.content.clone(),
+        })
+        .collect();
 
-    String::from_utf8(content.formatted()).unwrap()
+    common::mime::convert_to_mime(&mut thread_rng(), &form.message, &attachments).unwrap()
 }
 
 // functionality to parse a raw mime email message
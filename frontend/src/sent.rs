@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+use yew::format::{Json, Text};
+use yew::services::storage::{Area, StorageService};
+
+use common::SendResult;
+
+const STORAGE_KEY: &str = "tguard.sent";
+
+/// Message sent from this browser, kept to show its status later on.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SentRecord {
+    pub id: String,
+    pub to: String,
+    pub subject: String,
+    pub management_token: String,
+}
+
+/// Messages sent from this browser, most recent first.
+pub fn load_sent() -> Vec<SentRecord> {
+    let stored: Text = match StorageService::new(Area::Local) {
+        Ok(storage) => storage.restore(STORAGE_KEY),
+        Err(_) => return vec![],
+    };
+
+    stored
+        .ok()
+        .and_then(|stored| serde_json::from_str(&stored).ok())
+        .unwrap_or_default()
+}
+
+/// Remember the messages in a send result, along with their management tokens.
+/// The copy for the sender is not a recipient, so it is left out.
+pub fn store_sent(subject: &str, result: &SendResult) {
+    if let Ok(mut storage) = StorageService::new(Area::Local) {
+        let mut records: Vec<SentRecord> = result
+            .accepted
+            .iter()
+            .filter(|sent| !sent.sender_copy)
+            .map(|sent| SentRecord {
+                id: sent.id.clone(),
+                to: sent.to.clone(),
+                subject: subject.to_owned(),
+                management_token: sent.management_token.clone(),
+            })
+            .collect();
+        records.extend(load_sent());
+        storage.store(STORAGE_KEY, Json(&records));
+    }
+}
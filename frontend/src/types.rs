@@ -1,4 +1,4 @@
-use common::{AttributeIdentifier, AttributeValue};
+use common::{AttributeIdentifier, AttributeValue, Channel, DownloadLimit, Locale};
 use serde::{Deserialize, Serialize};
 use yew::services::reader::FileData;
 
@@ -7,6 +7,10 @@ use crate::attributes::EMAIL_ATTRIBUTE_IDENTIFIER;
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Recipient {
     pub to: String,
+    /// How the recipient is notified, `to` is an email address or a phone
+    /// number accordingly
+    #[serde(default)]
+    pub channel: Channel,
     pub attributes: Vec<AttributeValue>,
 }
 
@@ -14,6 +18,7 @@ impl Default for Recipient {
     fn default() -> Self {
         Recipient {
             to: Default::default(),
+            channel: Channel::Email,
             attributes: vec![
                 AttributeValue {
                     identifier: AttributeIdentifier(EMAIL_ATTRIBUTE_IDENTIFIER.to_owned()),
@@ -32,6 +37,16 @@ pub struct FormData {
     pub subject: String,
     pub message: String,
     pub attachments: Vec<FileData>,
+    pub download_limit: Option<DownloadLimit>,
+    /// Language of the notification emails, derived from each recipient's
+    /// address when not chosen
+    pub locale: Option<Locale>,
+    /// Only send the download link, without attaching the encrypted message
+    pub link_only: bool,
+    /// Do not notify the recipients, the sender hands over the download links
+    pub share_link: bool,
+    /// Also seal the message for the sender and send them the copy
+    pub sender_copy: bool,
 }
 
 impl Default for FormData {
@@ -42,6 +57,11 @@ impl Default for FormData {
             subject: Default::default(),
             message: Default::default(),
             attachments: vec![],
+            download_limit: None,
+            locale: None,
+            link_only: false,
+            share_link: false,
+            sender_copy: false,
         }
     }
 }
@@ -62,4 +82,7 @@ pub struct ReceivedData {
     pub attachments: Vec<File>,
     pub attributes: Vec<AttributeValue>,
     pub signed: bool,
+    /// Hash of the sealed message, covered by a receipt
+    pub hash: String,
+    pub download_limit: Option<DownloadLimit>,
 }
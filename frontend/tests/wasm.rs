@@ -1,7 +1,10 @@
 use regex::Regex;
 use wasm_bindgen_test::*;
 
+use common::Locale;
 use tguard_frontend::{
+    actions::SendError,
+    i18n::{self, Text, Translate},
     mime::{convert_from_mime, convert_to_mime},
     types::{FormData, Recipient},
 };
@@ -25,11 +28,17 @@ fn test_convert_to_mime() {
         from: String::default(),
         to: vec![Recipient {
             to: String::default(),
+            channel: Default::default(),
             attributes: vec![],
         }],
         subject: "Test subject".to_owned(),
         message: "This is a test message.\nKind regards".to_owned(),
         attachments: vec![],
+        download_limit: None,
+        locale: None,
+        link_only: false,
+        share_link: false,
+        sender_copy: false,
     };
 
     let mail = replace_boundary(&convert_to_mime(&form_data));
@@ -426,3 +435,17 @@ DQo=
     );
     assert_eq!(mail.1[0].mimetype, "message/rfc822");
 }
+
+#[wasm_bindgen_test]
+fn test_translations() {
+    assert_eq!(Locale::En.t(Text::Send), "Send");
+    assert_eq!(Locale::Nl.t(Text::Send), "Versturen");
+
+    i18n::set_current(Locale::En);
+    assert_eq!(SendError::NotSent.to_string(), "message could not be sent");
+    i18n::set_current(Locale::Nl);
+    assert_eq!(
+        SendError::NotSent.to_string(),
+        "het bericht kon niet worden verstuurd"
+    );
+}